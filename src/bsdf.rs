@@ -0,0 +1,455 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::{rand::NormalDist, scene::mesh::SurfaceMaterial, vector::Vector3};
+
+/// orthonormal basis around a shading normal, local z is the normal
+#[derive(Debug, Clone)]
+pub struct Frame {
+    s: Vector3<f64>,
+    t: Vector3<f64>,
+    n: Vector3<f64>,
+}
+
+impl Frame {
+    // https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+    pub fn new(n: &Vector3<f64>) -> Frame {
+        let sign = 1.0_f64.copysign(n.z());
+        let a = -1.0 / (sign + n.z());
+        let b = n.x() * n.y() * a;
+        Frame {
+            s: Vector3::new(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x()),
+            t: Vector3::new(b, sign + n.y() * n.y() * a, -n.y()),
+            n: n.clone(),
+        }
+    }
+
+    pub fn to_local(&self, v: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(v.dot(&self.s), v.dot(&self.t), v.dot(&self.n))
+    }
+
+    pub fn to_world(&self, v: &Vector3<f64>) -> Vector3<f64> {
+        &self.s * v.x() + &self.t * v.y() + &self.n * v.z()
+    }
+}
+
+#[derive(Debug)]
+pub struct BsdfSample {
+    pub dir: Vector3<f64>,
+    /// bsdf * cos / pdf
+    pub weight: Vector3<f64>,
+    pub pdf: f64,
+    /// sampled from a delta distribution, `pdf` is meaningless and light sampling can't
+    /// reach this direction
+    pub delta: bool,
+}
+
+fn sample_cosine_hemisphere(rand_state: &mut NormalDist) -> Vector3<f64> {
+    let r = rand_state.uniform().sqrt();
+    let phi = TAU * rand_state.uniform();
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - r * r).max(0.0).sqrt())
+}
+
+/// unpolarized fresnel reflectance of a dielectric interface, `eta` is the ratio of the
+/// indices of refraction on the far and near side
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0);
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+/// trowbridge-reitz (ggx) microfacet distribution, directions are in the local frame
+/// https://www.pbr-book.org/4ed/Reflection_Models/Roughness_Using_Microfacet_Theory
+#[derive(Debug, Clone, Copy)]
+struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    fn d(&self, h: &Vector3<f64>) -> f64 {
+        let a2 = self.alpha * self.alpha;
+        let denom = h.z() * h.z() * (a2 - 1.0) + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    fn lambda(&self, w: &Vector3<f64>) -> f64 {
+        let cos2 = w.z() * w.z();
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    fn g1(&self, w: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    fn g(&self, wo: &Vector3<f64>, wi: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// density of the visible normal `h` as seen from `wo`
+    fn visible_pdf(&self, wo: &Vector3<f64>, h: &Vector3<f64>) -> f64 {
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+
+    /// samples a microfacet normal visible from `wo`
+    /// https://jcgt.org/published/0007/04/01/
+    fn sample_visible(&self, wo: &Vector3<f64>, rand_state: &mut NormalDist) -> Vector3<f64> {
+        let vh = Vector3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).normalized();
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vector3::new(-vh.y(), vh.x(), 0.0) * (1.0 / len2.sqrt())
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = t1.cross(&vh);
+
+        let r = rand_state.uniform().sqrt();
+        let phi = TAU * rand_state.uniform();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        Vector3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).normalized()
+    }
+}
+
+fn reflect(w: &Vector3<f64>, n: &Vector3<f64>) -> Vector3<f64> {
+    n * (2.0 * w.dot(n)) - w
+}
+
+fn schlick(f0: &Vector3<f64>, cos: f64) -> Vector3<f64> {
+    let k = (1.0 - cos).clamp(0.0, 1.0).powi(5);
+    Vector3::new(1.0, 1.0, 1.0) * k + f0 * (1.0 - k)
+}
+
+// index of refraction of the coating on top of a glossy material
+const COATING_IOR: f64 = 1.5;
+
+#[derive(Debug)]
+enum Lobes {
+    /// lambertian base under a glossy ggx lobe tinted by `Ks`, the same model as pbrt's
+    /// plastic
+    Plastic { ggx: Ggx, specular_prob: f64 },
+    /// smooth glass, `eta` is the ratio of the indices on the far and near side
+    Dielectric { eta: f64 },
+    /// smooth mirror with schlick fresnel starting at `Ks`
+    Mirror,
+}
+
+/// scattering at a single surface point, all directions point away from the surface
+#[derive(Debug)]
+pub struct Bsdf<'a> {
+    material: &'a SurfaceMaterial<'a>,
+    frame: Frame,
+    /// true surface orientation, on the same side as the outgoing direction
+    geometric_normal: Vector3<f64>,
+    lobes: Lobes,
+}
+
+impl<'a> Bsdf<'a> {
+    /// both normals have to be on the same side as the outgoing direction, `entering`
+    /// tells whether that is the front of the surface. scattering follows the shading
+    /// normal, but reflections never go below the geometric one and transmissions never
+    /// above it, otherwise interpolated normals leak light through the mesh
+    pub fn new(
+        material: &'a SurfaceMaterial<'a>,
+        shading_normal: &Vector3<f64>,
+        geometric_normal: &Vector3<f64>,
+        entering: bool,
+    ) -> Bsdf<'a> {
+        let lobes = if material.base.is_dielectric() {
+            let eta = if entering {
+                material.base.density
+            } else {
+                1.0 / material.base.density
+            };
+            Lobes::Dielectric { eta }
+        } else if material.base.is_mirror() {
+            Lobes::Mirror
+        } else {
+            let diffuse_weight = material.diffuse.x() + material.diffuse.y() + material.diffuse.z();
+            let specular_weight =
+                material.specular.x() + material.specular.y() + material.specular.z();
+            let specular_prob = if specular_weight > 0.0 {
+                specular_weight / (diffuse_weight + specular_weight)
+            } else {
+                0.0
+            };
+            Lobes::Plastic {
+                ggx: Ggx {
+                    alpha: material.roughness(),
+                },
+                specular_prob,
+            }
+        };
+        Bsdf {
+            material,
+            frame: Frame::new(shading_normal),
+            geometric_normal: geometric_normal.clone(),
+            lobes,
+        }
+    }
+
+    /// true if every direction `sample` returns is a delta
+    pub fn is_delta(&self) -> bool {
+        !matches!(self.lobes, Lobes::Plastic { .. })
+    }
+
+    /// bsdf times the cosine term, for light arriving from `wi` and leaving along `wo`
+    pub fn eval(&self, wo: &Vector3<f64>, wi: &Vector3<f64>) -> Vector3<f64> {
+        let Lobes::Plastic { ggx, specular_prob } = &self.lobes else {
+            return Vector3::default();
+        };
+        if wi.dot(&self.geometric_normal) <= 0.0 {
+            return Vector3::default();
+        }
+        let wo = self.frame.to_local(wo);
+        let wi = self.frame.to_local(wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Vector3::default();
+        }
+        let mut result = &self.material.diffuse * (wi.z() / PI);
+        if *specular_prob > 0.0 {
+            let h = (&wo + &wi).normalized();
+            let f = fresnel_dielectric(wi.dot(&h), COATING_IOR);
+            let spec = ggx.d(&h) * ggx.g(&wo, &wi) * f / (4.0 * wo.z());
+            result += &self.material.specular * spec;
+        }
+        result
+    }
+
+    /// solid angle density with which `sample` picks `wi`
+    pub fn pdf(&self, wo: &Vector3<f64>, wi: &Vector3<f64>) -> f64 {
+        let Lobes::Plastic { ggx, specular_prob } = &self.lobes else {
+            return 0.0;
+        };
+        if wi.dot(&self.geometric_normal) <= 0.0 {
+            return 0.0;
+        }
+        let wo = self.frame.to_local(wo);
+        let wi = self.frame.to_local(wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let mut pdf = (1.0 - specular_prob) * wi.z() / PI;
+        if *specular_prob > 0.0 {
+            let h = (&wo + &wi).normalized();
+            pdf += specular_prob * ggx.visible_pdf(&wo, &h) / (4.0 * wo.dot(&h));
+        }
+        pdf
+    }
+
+    pub fn sample(&self, wo: &Vector3<f64>, rand_state: &mut NormalDist) -> Option<BsdfSample> {
+        let wo_local = self.frame.to_local(wo);
+        if wo_local.z() <= 0.0 {
+            return None;
+        }
+        match &self.lobes {
+            Lobes::Plastic { ggx, specular_prob } => {
+                let wi = if rand_state.uniform() < *specular_prob {
+                    let h = ggx.sample_visible(&wo_local, rand_state);
+                    reflect(&wo_local, &h)
+                } else {
+                    sample_cosine_hemisphere(rand_state)
+                };
+                if wi.z() <= 0.0 {
+                    return None;
+                }
+
+                let wi = self.frame.to_world(&wi);
+                let pdf = self.pdf(wo, &wi);
+                if pdf <= 0.0 {
+                    return None;
+                }
+                Some(BsdfSample {
+                    weight: self.eval(wo, &wi) * (1.0 / pdf),
+                    dir: wi,
+                    pdf,
+                    delta: false,
+                })
+            }
+            Lobes::Mirror => {
+                let wi = Vector3::new(-wo_local.x(), -wo_local.y(), wo_local.z());
+                let wi = self.frame.to_world(&wi);
+                if wi.dot(&self.geometric_normal) <= 0.0 {
+                    return None;
+                }
+                Some(BsdfSample {
+                    dir: wi,
+                    weight: schlick(&self.material.specular, wo_local.z()),
+                    pdf: 1.0,
+                    delta: true,
+                })
+            }
+            Lobes::Dielectric { eta } => {
+                let cos_i = wo_local.z();
+                let reflectance = fresnel_dielectric(cos_i, *eta);
+                if rand_state.uniform() < reflectance {
+                    let wi = Vector3::new(-wo_local.x(), -wo_local.y(), cos_i);
+                    let wi = self.frame.to_world(&wi);
+                    if wi.dot(&self.geometric_normal) <= 0.0 {
+                        return None;
+                    }
+                    return Some(BsdfSample {
+                        dir: wi,
+                        weight: Vector3::new(1.0, 1.0, 1.0),
+                        pdf: reflectance,
+                        delta: true,
+                    });
+                }
+                // snell's law, reflectance is 1 under total internal reflection so the
+                // root is real here
+                let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+                let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+                let wi = -(&wo_local * (1.0 / eta)) + Vector3::new(0.0, 0.0, cos_i / eta - cos_t);
+                let wi = self.frame.to_world(&wi);
+                if wi.dot(&self.geometric_normal) >= 0.0 {
+                    return None;
+                }
+                // radiance is compressed into a smaller solid angle when entering a denser
+                // medium
+                Some(BsdfSample {
+                    dir: wi,
+                    weight: &self.material.base.filter * (1.0 / (eta * eta)),
+                    pdf: 1.0 - reflectance,
+                    delta: true,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::mesh::Material;
+
+    const SAMPLES: usize = 200_000;
+
+    fn up() -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 1.0)
+    }
+
+    /// `eval` integrated over the hemisphere with uniformly distributed directions, and
+    /// the same integral estimated with the bsdf's own samples
+    fn albedo(bsdf: &Bsdf, wo: &Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+        let mut rand_state = NormalDist::from_stream(5, 0);
+        let mut uniform = Vector3::default();
+        let mut sampled = Vector3::default();
+        for _ in 0..SAMPLES {
+            let z = rand_state.uniform();
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = TAU * rand_state.uniform();
+            let wi = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+            uniform += bsdf.eval(wo, &wi) * TAU;
+            if let Some(sample) = bsdf.sample(wo, &mut rand_state) {
+                sampled += sample.weight;
+            }
+        }
+        let n = 1.0 / SAMPLES as f64;
+        (uniform * n, sampled * n)
+    }
+
+    #[test]
+    fn plastic_samples_match_eval() {
+        let material = Material {
+            diffuse: Vector3::new(0.5, 0.3, 0.1),
+            specular: Vector3::new(0.4, 0.4, 0.4),
+            specular_exp: 50.0,
+            ..Default::default()
+        };
+        let surface = SurfaceMaterial::new(&material);
+        let bsdf = Bsdf::new(&surface, &up(), &up(), true);
+        let wo = Vector3::new(0.6, 0.0, 0.8);
+        let (uniform, sampled) = albedo(&bsdf, &wo);
+        for c in 0..3 {
+            assert!(
+                (uniform[c] - sampled[c]).abs() < 0.02 * uniform[c].max(0.1),
+                "{uniform:?} {sampled:?}"
+            );
+            // nothing reflects more than arrives
+            assert!(sampled[c] <= 1.0, "{sampled:?}");
+        }
+
+        // `pdf` is the density `sample` draws from
+        let mut rand_state = NormalDist::from_stream(6, 0);
+        let mut total = 0.0;
+        for _ in 0..SAMPLES {
+            if let Some(sample) = bsdf.sample(&wo, &mut rand_state) {
+                assert!((sample.pdf - bsdf.pdf(&wo, &sample.dir)).abs() < 1e-9 * sample.pdf);
+                total += 1.0;
+            }
+        }
+        let mut integral = 0.0;
+        for _ in 0..SAMPLES {
+            let z = rand_state.uniform();
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = TAU * rand_state.uniform();
+            integral += bsdf.pdf(&wo, &Vector3::new(r * phi.cos(), r * phi.sin(), z)) * TAU;
+        }
+        let (total, integral) = (total / SAMPLES as f64, integral / SAMPLES as f64);
+        assert!((total - integral).abs() < 0.02, "{total} {integral}");
+    }
+
+    #[test]
+    fn white_lambertian_reflects_everything() {
+        let material = Material {
+            diffuse: Vector3::new(1.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        let surface = SurfaceMaterial::new(&material);
+        let bsdf = Bsdf::new(&surface, &up(), &up(), true);
+        let (uniform, sampled) = albedo(&bsdf, &Vector3::new(0.0, 0.6, 0.8));
+        assert!((uniform.x() - 1.0).abs() < 0.01, "{uniform:?}");
+        assert!((sampled.x() - 1.0).abs() < 1e-9, "{sampled:?}");
+    }
+
+    #[test]
+    fn refraction_is_scaled_by_filter_over_eta_squared() {
+        let material = Material {
+            filter: Vector3::new(0.9, 0.8, 0.7),
+            density: 1.5,
+            illum: 7,
+            ..Default::default()
+        };
+        let wo = Vector3::new(0.6, 0.0, 0.8);
+        for (entering, eta) in [(true, 1.5), (false, 1.0 / 1.5)] {
+            let surface = SurfaceMaterial::new(&material);
+            let bsdf = Bsdf::new(&surface, &up(), &up(), entering);
+            let mut rand_state = NormalDist::from_stream(7, 0);
+            let (mut reflected, mut refracted) = (0, 0);
+            for _ in 0..1000 {
+                let Some(sample) = bsdf.sample(&wo, &mut rand_state) else {
+                    continue;
+                };
+                assert!(sample.delta);
+                if sample.dir.z() > 0.0 {
+                    reflected += 1;
+                    assert_eq!(sample.weight.x(), 1.0);
+                    continue;
+                }
+                refracted += 1;
+                let expected = &material.filter * (1.0 / (eta * eta));
+                assert!(
+                    (&sample.weight - &expected).len() < 1e-12,
+                    "{:?}",
+                    sample.weight
+                );
+                // snell's law
+                let sin_t = (1.0 - sample.dir.z() * sample.dir.z()).sqrt();
+                assert!((sin_t * eta - 0.6).abs() < 1e-9);
+            }
+            assert!(reflected > 0 && refracted > 0, "{reflected} {refracted}");
+        }
+    }
+}
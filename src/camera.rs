@@ -0,0 +1,244 @@
+use crate::{rand::NormalDist, ray::Ray, vector::Vector3};
+
+/// thin lens camera looking from `eye` towards `target`, a pinhole unless `set_lens` gives
+/// it an aperture
+#[derive(Debug, Clone)]
+pub struct Camera {
+    eye: Vector3<f64>,
+    target: Vector3<f64>,
+    up: Vector3<f64>,
+    /// vertical field of view in degrees
+    vfov: f64,
+    resolution: (usize, usize),
+    aperture_radius: f64,
+    /// distance along the view direction that is in perfect focus
+    focus_distance: f64,
+    /// number of aperture blades, 0 for a round aperture
+    blades: u32,
+    // orthonormal basis, right x forward = up
+    forward: Vector3<f64>,
+    right: Vector3<f64>,
+    true_up: Vector3<f64>,
+    // half extents of the image plane at distance 1
+    half_size: (f64, f64),
+}
+
+impl Camera {
+    /// an `up` along the view direction is replaced by the world axis furthest from it.
+    /// panics if `eye` and `target` are the same point, `vfov` is outside of (0, 180) or
+    /// the resolution is zero in either direction
+    pub fn new(
+        eye: Vector3<f64>,
+        target: Vector3<f64>,
+        up: Vector3<f64>,
+        vfov: f64,
+        resolution: (usize, usize),
+    ) -> Camera {
+        assert!(
+            resolution.0 > 0 && resolution.1 > 0,
+            "camera resolution {resolution:?} is empty"
+        );
+        assert!(
+            vfov > 0.0 && vfov < 180.0,
+            "vertical field of view {vfov} is outside of (0, 180)"
+        );
+        let view = &target - &eye;
+        assert!(view.len() > 0.0, "camera eye and target are the same point");
+        let forward = view.normalized();
+        let mut right = up.cross(&forward);
+        if right.len() <= 1e-9 * up.len() {
+            let axis = (0..3)
+                .min_by(|&a, &b| forward[a].abs().total_cmp(&forward[b].abs()))
+                .unwrap();
+            let mut fallback = Vector3::default();
+            fallback[axis] = 1.0;
+            right = fallback.cross(&forward);
+        }
+        let right = right.normalized();
+        let true_up = forward.cross(&right);
+        let half_height = (vfov.to_radians() / 2.0).tan();
+        let aspect = resolution.0 as f64 / resolution.1 as f64;
+        Camera {
+            focus_distance: (&target - &eye).len(),
+            eye,
+            target,
+            up,
+            vfov,
+            resolution,
+            aperture_radius: 0.0,
+            blades: 0,
+            forward,
+            right,
+            true_up,
+            half_size: (half_height * aspect, half_height),
+        }
+    }
+
+    /// a radius of 0 or less is a pinhole. panics if `focus_distance` is not positive and
+    /// finite
+    pub fn set_lens(&mut self, aperture_radius: f64, focus_distance: f64) {
+        assert!(
+            focus_distance > 0.0 && focus_distance.is_finite(),
+            "focus distance {focus_distance} is not positive and finite"
+        );
+        self.aperture_radius = aperture_radius.max(0.0);
+        self.focus_distance = focus_distance;
+    }
+
+    /// polygonal aperture with this many straight blades, which shapes the bokeh
+    pub fn set_blades(&mut self, blades: u32) {
+        self.blades = if blades < 3 { 0 } else { blades };
+    }
+
+    pub fn aperture_radius(&self) -> f64 {
+        self.aperture_radius
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focus_distance
+    }
+
+    pub fn blades(&self) -> u32 {
+        self.blades
+    }
+
+    pub fn eye(&self) -> &Vector3<f64> {
+        &self.eye
+    }
+
+    pub fn target(&self) -> &Vector3<f64> {
+        &self.target
+    }
+
+    pub fn up(&self) -> &Vector3<f64> {
+        &self.up
+    }
+
+    pub fn vfov(&self) -> f64 {
+        self.vfov
+    }
+
+    pub fn resolution(&self) -> (usize, usize) {
+        self.resolution
+    }
+
+    /// ray through a position on the film in pixels, (0, 0) is the upper left corner
+    pub fn generate_ray(&self, film: (f64, f64), rand_state: &mut NormalDist) -> Ray {
+        let ndc_x = 2.0 * film.0 / self.resolution.0 as f64 - 1.0;
+        let ndc_y = 1.0 - 2.0 * film.1 / self.resolution.1 as f64;
+        let dir = &self.forward
+            + &self.right * (ndc_x * self.half_size.0)
+            + &self.true_up * (ndc_y * self.half_size.1);
+        if self.aperture_radius <= 0.0 {
+            return Ray::new(self.eye.clone(), dir.normalized());
+        }
+
+        // every ray through the pixel meets on the plane of focus, `dir` has a forward
+        // component of 1
+        let focus_point = &self.eye + &dir * self.focus_distance;
+        let (lens_x, lens_y) = if self.blades == 0 {
+            rand_state.disk()
+        } else {
+            rand_state.polygon(self.blades, 0.0)
+        };
+        let orig = &self.eye
+            + &self.right * (lens_x * self.aperture_radius)
+            + &self.true_up * (lens_y * self.aperture_radius);
+        let dir = (focus_point - &orig).normalized();
+        Ray::new(orig, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Vector3::new(0.0, -10.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            40.0,
+            (8, 6),
+        )
+    }
+
+    /// position of a lens sample in units of the aperture radius, on the right and up axes
+    fn lens_position(camera: &Camera, ray: &Ray) -> (f64, f64) {
+        let offset = ray.orig() - &camera.eye;
+        assert!(offset.dot(&camera.forward).abs() < 1e-12);
+        (
+            offset.dot(&camera.right) / camera.aperture_radius,
+            offset.dot(&camera.true_up) / camera.aperture_radius,
+        )
+    }
+
+    #[test]
+    fn lens_samples_stay_inside_the_aperture() {
+        let mut rand = NormalDist::new([1, 2]);
+        let mut round = camera();
+        round.set_lens(0.5, 25.0);
+        for _ in 0..10_000 {
+            let (x, y) = lens_position(&round, &round.generate_ray((3.5, 2.5), &mut rand));
+            assert!(x.hypot(y) <= 1.0 + 1e-12, "{x} {y}");
+        }
+
+        // inside every edge of a hexagon with a corner on the right axis
+        let mut bladed = round.clone();
+        bladed.set_blades(6);
+        let edges = 6;
+        let inradius = (std::f64::consts::PI / edges as f64).cos();
+        for _ in 0..10_000 {
+            let (x, y) = lens_position(&bladed, &bladed.generate_ray((3.5, 2.5), &mut rand));
+            for edge in 0..edges {
+                let angle = std::f64::consts::TAU * (edge as f64 + 0.5) / edges as f64;
+                assert!(
+                    x * angle.cos() + y * angle.sin() <= inradius + 1e-12,
+                    "{x} {y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rays_of_a_pixel_meet_on_the_focus_plane() {
+        let mut rand = NormalDist::new([3, 4]);
+        let pinhole = camera();
+        let mut lens = camera();
+        lens.set_lens(0.5, 25.0);
+        lens.set_blades(5);
+        let film = (1.25, 4.5);
+
+        let center = pinhole.generate_ray(film, &mut rand);
+        let expected = center.point_at(25.0 / center.dir().dot(&pinhole.forward));
+        for _ in 0..100 {
+            let ray = lens.generate_ray(film, &mut rand);
+            // the lens is perpendicular to the view direction, so is the focus plane
+            let focus = ray.point_at(25.0 / ray.dir().dot(&lens.forward));
+            assert!((&focus - &expected).len() < 1e-9, "{focus:?} {expected:?}");
+        }
+    }
+
+    #[test]
+    fn closed_aperture_is_a_pinhole() {
+        let mut rand = NormalDist::new([5, 6]);
+        let pinhole = camera();
+        let mut closed = camera();
+        closed.set_lens(0.0, 3.0);
+        closed.set_blades(7);
+        for film in [(0.0, 0.0), (4.0, 3.0), (7.5, 0.25)] {
+            let a = pinhole.generate_ray(film, &mut rand);
+            let b = closed.generate_ray(film, &mut rand);
+            for i in 0..3 {
+                assert_eq!(a.orig()[i].to_bits(), b.orig()[i].to_bits());
+                assert_eq!(a.dir()[i].to_bits(), b.dir()[i].to_bits());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "focus distance")]
+    fn focus_distance_has_to_be_positive() {
+        camera().set_lens(1.0, 0.0);
+    }
+}
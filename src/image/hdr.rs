@@ -0,0 +1,358 @@
+//! lossless floating point output formats
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExrPixelType {
+    #[default]
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn id(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        }
+    }
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, std::io::Error> {
+    let file = File::options()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+/// ieee 754 binary16 bits of `x`, rounded to nearest even
+fn f32_to_half(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        // inf stays inf, nan stays a quiet nan
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exp = exp - 127 + 15;
+    if half_exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exp <= 0 {
+        if half_exp < -10 {
+            return sign;
+        }
+        // subnormal half, shift the implicit bit in
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exp) as u32;
+        let half_mantissa = mantissa >> shift;
+        let rest = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = rest > halfway || (rest == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round as u32) as u16;
+    }
+    let half = ((half_exp as u32) << 10) | (mantissa >> 13);
+    let rest = mantissa & 0x1fff;
+    let round = rest > 0x1000 || (rest == 0x1000 && half & 1 == 1);
+    // a carry out of the mantissa correctly bumps the exponent, up to inf
+    sign | (half + round as u32) as u16
+}
+
+/// shared exponent encoding used by radiance files
+fn rgbe(rgb: [f32; 3]) -> [u8; 4] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    if max.is_nan() || max < 1e-32 {
+        return [0; 4];
+    }
+    let max = max.min(f32::MAX);
+    // frexp: max = mantissa * 2^exp with mantissa in [0.5, 1)
+    let exp = max.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(exp);
+    let channel = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        channel(rgb[0]),
+        channel(rgb[1]),
+        channel(rgb[2]),
+        (exp + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+impl Image {
+    fn rgb_f32(&self, x: usize, y: usize) -> [f32; 3] {
+        let p = &self[(x, y)];
+        [p[0] as f32, p[1] as f32, p[2] as f32]
+    }
+
+    /// portable float map, little endian with the rows stored bottom to top
+    pub fn write_to_pfm(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut w = create(path)?;
+        self.write_pfm(&mut w)?;
+        w.flush()
+    }
+
+    fn write_pfm(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
+        write!(w, "PF\n{} {}\n-1.0\n", self.shape.0, self.shape.1)?;
+        for y in (0..self.shape.1).rev() {
+            for x in 0..self.shape.0 {
+                for c in self.rgb_f32(x, y) {
+                    w.write_all(&c.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// radiance rgbe, written as flat scanlines which every reader accepts
+    pub fn write_to_hdr(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut w = create(path)?;
+        write!(
+            w,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.shape.1, self.shape.0
+        )?;
+        for y in 0..self.shape.1 {
+            for x in 0..self.shape.0 {
+                w.write_all(&rgbe(self.rgb_f32(x, y)))?;
+            }
+        }
+        w.flush()
+    }
+
+    /// uncompressed single part scanline openexr
+    /// https://openexr.com/en/latest/OpenEXRFileLayout.html
+    pub fn write_to_exr(&self, path: &str, pixel_type: ExrPixelType) -> Result<(), std::io::Error> {
+        let mut w = create(path)?;
+        self.write_exr(&mut w, pixel_type)?;
+        w.flush()
+    }
+
+    fn write_exr(
+        &self,
+        w: &mut impl Write,
+        pixel_type: ExrPixelType,
+    ) -> Result<(), std::io::Error> {
+        let (width, height) = self.shape;
+        let mut header = Vec::new();
+        header.extend_from_slice(&20000630_i32.to_le_bytes());
+        header.extend_from_slice(&2_i32.to_le_bytes());
+
+        // channels have to be sorted by name
+        let mut channels = Vec::new();
+        for name in ["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&pixel_type.id().to_le_bytes());
+            // linear flag and three reserved bytes
+            channels.extend_from_slice(&[0, 0, 0, 0]);
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+        }
+        channels.push(0);
+        exr_attribute(&mut header, "channels", "chlist", &channels);
+        exr_attribute(&mut header, "compression", "compression", &[0]);
+        let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        exr_attribute(&mut header, "dataWindow", "box2i", &window);
+        exr_attribute(&mut header, "displayWindow", "box2i", &window);
+        exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        exr_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1_f32.to_le_bytes(),
+        );
+        exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        exr_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1_f32.to_le_bytes(),
+        );
+        header.push(0);
+
+        // without compression every block is a single scanline of known size
+        let line_size = width * 3 * pixel_type.size();
+        let block_size = 8 + line_size;
+        let first_block = header.len() + 8 * height;
+
+        w.write_all(&header)?;
+        for y in 0..height {
+            w.write_all(&((first_block + y * block_size) as u64).to_le_bytes())?;
+        }
+        for y in 0..height {
+            w.write_all(&(y as i32).to_le_bytes())?;
+            w.write_all(&(line_size as i32).to_le_bytes())?;
+            for c in [2, 1, 0] {
+                for x in 0..width {
+                    let value = self.rgb_f32(x, y)[c];
+                    match pixel_type {
+                        ExrPixelType::Half => w.write_all(&f32_to_half(value).to_le_bytes())?,
+                        ExrPixelType::Float => w.write_all(&value.to_le_bytes())?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        // largest finite half, and the first value that rounds past it
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(65519.0), 0x7bff);
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xfc00);
+        let nan = f32_to_half(f32::NAN);
+        assert_eq!(nan & 0x7c00, 0x7c00);
+        assert_ne!(nan & 0x3ff, 0);
+        // smallest subnormal, and halfway to it which ties to the even zero
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(3.0 * 2f32.powi(-25)), 0x0002);
+        // largest subnormal rounding up into the smallest normal
+        assert_eq!(f32_to_half(2f32.powi(-14) * (1.0 - 2f32.powi(-12))), 0x0400);
+        // ties between two normals go to the even mantissa
+        assert_eq!(f32_to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    }
+
+    #[test]
+    fn rgbe_round_trips() {
+        let decode = |[r, g, b, e]: [u8; 4]| {
+            if e == 0 {
+                return [0.0; 3];
+            }
+            let scale = 2f32.powi(e as i32 - 128 - 8);
+            [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+        };
+        for rgb in [
+            [1.0f32, 0.5, 0.25],
+            [1000.0, 3.0, 0.0],
+            [0.001, 0.002, 0.003],
+            [0.5, 0.5, 0.5],
+        ] {
+            let max = rgb[0].max(rgb[1]).max(rgb[2]);
+            let decoded = decode(rgbe(rgb));
+            for c in 0..3 {
+                // 8 bits of mantissa shared by the largest channel
+                assert!(
+                    (decoded[c] - rgb[c]).abs() <= max / 128.0,
+                    "{rgb:?} {decoded:?}"
+                );
+            }
+        }
+        assert_eq!(rgbe([0.0; 3]), [0; 4]);
+        assert_eq!(rgbe([f32::NAN; 3]), [0; 4]);
+    }
+
+    fn gradient() -> Image {
+        let mut image = Image::new((3, 2));
+        for x in 0..3 {
+            for y in 0..2 {
+                image[(x, y)] = Vector3::new(x as f64, y as f64, 0.25);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        let image = gradient();
+        let mut bytes = Vec::new();
+        image.write_pfm(&mut bytes).unwrap();
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let floats: Vec<f32> = bytes[header.len()..]
+            .chunks(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats.len(), 3 * 2 * 3);
+        // bottom row first
+        for (i, rgb) in floats.chunks(3).enumerate() {
+            let (x, y) = (i % 3, 1 - i / 3);
+            let expected = &image[(x, y)];
+            for c in 0..3 {
+                assert_eq!(rgb[c] as f64, expected[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_header_and_offsets() {
+        let image = gradient();
+        for (pixel_type, size) in [(ExrPixelType::Half, 2), (ExrPixelType::Float, 4)] {
+            let mut bytes = Vec::new();
+            image.write_exr(&mut bytes, pixel_type).unwrap();
+            let int = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+            assert_eq!(int(0), 20000630);
+            assert_eq!(int(4), 2);
+            assert_eq!(&bytes[8..17], b"channels\0");
+
+            // the header ends with an empty attribute name right before the offsets
+            let line_size = 3 * 3 * size;
+            let table = bytes.len() - 2 * (8 + line_size) - 2 * 8;
+            assert_eq!(bytes[table - 1], 0);
+            for y in 0..2 {
+                let at = table + 8 * y;
+                let offset = u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+                assert_eq!(offset, table + 16 + y * (8 + line_size));
+                assert_eq!(int(offset), y as i32);
+                assert_eq!(int(offset + 4), line_size as i32);
+            }
+            // channels are stored b, g, r, the blue of every pixel is 0.25
+            let first = table + 16 + 8;
+            let blue = match pixel_type {
+                ExrPixelType::Half => u16::from_le_bytes([bytes[first], bytes[first + 1]]) as u32,
+                ExrPixelType::Float => {
+                    u32::from_le_bytes(bytes[first..first + 4].try_into().unwrap())
+                }
+            };
+            let expected = match pixel_type {
+                ExrPixelType::Half => f32_to_half(0.25) as u32,
+                ExrPixelType::Float => 0.25f32.to_bits(),
+            };
+            assert_eq!(blue, expected);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+mod hdr;
+mod tonemap;
+
+pub use hdr::ExrPixelType;
+pub use tonemap::{srgb_oetf, ToneMap, ToneMapping};
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    ops::{Index, IndexMut},
+};
+
+use crate::{filter::Filter, vector::Vector3};
+
+#[derive(Debug)]
+pub struct Image {
+    pixels: Vec<Vector3<f64>>,
+    /// sum of filter weights splatted into each pixel, until `resolve` divides them out
+    weights: Vec<f64>,
+    shape: (usize, usize),
+}
+
+impl Image {
+    pub fn new(shape: (usize, usize)) -> Image {
+        Image {
+            pixels: vec![Vector3::<f64>::default(); shape.0 * shape.1],
+            weights: vec![0.0; shape.0 * shape.1],
+            shape,
+        }
+    }
+
+    /// splats a sample at a position in pixels into every pixel `filter` reaches
+    pub fn add_sample(&mut self, pos: (f64, f64), value: &Vector3<f64>, filter: &Filter) {
+        let radius = filter.radius();
+        // pixel centers are at half integers
+        let x0 = (pos.0 - 0.5 - radius).ceil().max(0.0) as usize;
+        let y0 = (pos.1 - 0.5 - radius).ceil().max(0.0) as usize;
+        let x1 = ((pos.0 - 0.5 + radius).floor() + 1.0).clamp(0.0, self.shape.0 as f64) as usize;
+        let y1 = ((pos.1 - 0.5 + radius).floor() + 1.0).clamp(0.0, self.shape.1 as f64) as usize;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let weight = filter.eval(pos.0 - (x as f64 + 0.5), pos.1 - (y as f64 + 0.5));
+                if weight == 0.0 {
+                    continue;
+                }
+                let index = x + y * self.shape.0;
+                self.pixels[index] += value * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+
+    /// adds the unresolved samples of `other`, whose upper left pixel lands on `offset`
+    pub fn merge(&mut self, other: &Image, offset: (isize, isize)) {
+        for y in 0..other.shape.1 {
+            for x in 0..other.shape.0 {
+                let (tx, ty) = (x as isize + offset.0, y as isize + offset.1);
+                if tx < 0 || ty < 0 || tx >= self.shape.0 as isize || ty >= self.shape.1 as isize {
+                    continue;
+                }
+                let index = tx as usize + ty as usize * self.shape.0;
+                let other_index = x + y * other.shape.0;
+                self.pixels[index] += &other.pixels[other_index];
+                self.weights[index] += other.weights[other_index];
+            }
+        }
+    }
+
+    /// divides every pixel by the filter weight it received
+    pub fn resolve(&mut self) {
+        for (pixel, weight) in self.pixels.iter_mut().zip(self.weights.iter_mut()) {
+            // negative lobes can cancel out completely
+            if *weight > 1e-12 {
+                *pixel *= 1.0 / *weight;
+            } else {
+                *pixel = Vector3::default();
+            }
+            *weight = 1.0;
+        }
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// tone maps with the defaults, clamping at 1 like an hdr viewer would
+    pub fn write_to_png(&self, path: &str) -> Result<(), std::io::Error> {
+        self.write_to_png_with(path, &ToneMapping::default())
+    }
+
+    pub fn write_to_png_with(
+        &self,
+        path: &str,
+        tone_mapping: &ToneMapping,
+    ) -> Result<(), std::io::Error> {
+        let file = File::options()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+        let w = &mut BufWriter::new(file);
+        let mut encoder = png::Encoder::new(w, self.shape.0 as u32, self.shape.1 as u32);
+
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        // also writes the matching gAMA and cHRM chunks
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header()?;
+
+        writer.write_image_data(&tone_mapping.quantize(&self.pixels))?;
+
+        Ok(())
+    }
+}
+
+impl Index<(usize, usize)> for Image {
+    type Output = Vector3<f64>;
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.pixels[index.0 + index.1 * self.shape.0]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Image {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.pixels[index.0 + index.1 * self.shape.0]
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{rand::NormalDist, vector::Vector3};
+
+/// compresses scene referred radiance into [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMap {
+    /// values above 1 are cut off, matches what an hdr viewer shows at the same exposure
+    #[default]
+    Clamp,
+    /// luminance based reinhard, keeps hues while rolling off highlights
+    Reinhard,
+    /// stephen hill's fit of the aces reference rendering and sdr output transforms
+    Aces,
+}
+
+/// everything that happens between linear radiance and 8 bit srgb values
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToneMapping {
+    /// in stops, every pixel is scaled by 2^exposure before tone mapping
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    /// adds triangular noise of one quantization step to hide banding
+    pub dither: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            dither: true,
+        }
+    }
+}
+
+fn luminance(c: &Vector3<f64>) -> f64 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+fn mat_mul(m: &[[f64; 3]; 3], c: &Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(
+        m[0][0] * c[0] + m[0][1] * c[1] + m[0][2] * c[2],
+        m[1][0] * c[0] + m[1][1] * c[1] + m[1][2] * c[2],
+        m[2][0] * c[0] + m[2][1] * c[1] + m[2][2] * c[2],
+    )
+}
+
+// https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+const ACES_INPUT: [[f64; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+const ACES_OUTPUT: [[f64; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn aces(c: &Vector3<f64>) -> Vector3<f64> {
+    let c = mat_mul(&ACES_INPUT, c);
+    let rrt_odt = |x: f64| {
+        let a = x * (x + 0.0245786) - 0.000090537;
+        let b = x * (0.983729 * x + 0.4329510) + 0.238081;
+        a / b
+    };
+    mat_mul(
+        &ACES_OUTPUT,
+        &Vector3::new(rrt_odt(c[0]), rrt_odt(c[1]), rrt_odt(c[2])),
+    )
+}
+
+/// srgb opto-electronic transfer function, linear [0, 1] to encoded [0, 1]
+pub fn srgb_oetf(x: f64) -> f64 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMapping {
+    /// linear display referred color in [0, 1]
+    pub fn apply(&self, c: &Vector3<f64>) -> Vector3<f64> {
+        let c = c.apply(|x| if x.is_finite() { x.max(0.0) } else { 0.0 }) * self.exposure.exp2();
+        let mapped = match self.tone_map {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => {
+                let l = luminance(&c);
+                if l > 0.0 {
+                    &c * (1.0 / (1.0 + l))
+                } else {
+                    c
+                }
+            }
+            ToneMap::Aces => aces(&c),
+        };
+        mapped.apply(|x| x.clamp(0.0, 1.0))
+    }
+
+    /// 8 bit srgb encoded rgb triples, row by row
+    pub fn quantize(&self, pixels: &[Vector3<f64>]) -> Vec<u8> {
+        // fixed seed so the same image always encodes to the same bytes
+        let mut rand_state = NormalDist::from_stream(0, 0);
+        let mut result = Vec::with_capacity(pixels.len() * 3);
+        for p in pixels {
+            let mapped = self.apply(p);
+            for i in 0..3 {
+                let encoded = srgb_oetf(mapped[i]) * 255.0;
+                let noise = if self.dither {
+                    rand_state.uniform() - rand_state.uniform()
+                } else {
+                    0.0
+                };
+                result.push((encoded + noise).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+        result
+    }
+}
@@ -1,7 +1,13 @@
-mod image;
-mod obj;
+mod bsdf;
+pub mod camera;
+pub mod filter;
+pub mod image;
+pub mod obj;
 mod rand;
 mod ray;
+pub mod recorder;
 pub mod renderer;
-mod scene;
+pub mod scene;
+pub mod scene_file;
+pub mod texture;
 pub mod vector;
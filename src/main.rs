@@ -1,3 +1,413 @@
-fn main() {
-    println!("Hello, world!");
+use std::{ops::Range, path::Path, process::ExitCode, time::Instant};
+
+use ray_tracer::{
+    obj::{ObjWriter, PIXELS_PER_METER},
+    recorder::{ObjPathRecorder, PathFilter},
+    scene::Scene,
+    scene_file::{CameraDesc, InstanceDesc, MeshDesc, OutputDesc, SceneFile},
+};
+
+const USAGE: &str = "\
+usage:
+  ray-tracer render <scene.toml | model.obj> [options]
+      --resolution <width>x<height>
+      --spp <samples per pixel>
+      --max-depth <path segments>
+      --integrator <path | direct | normals>
+      --clamp <largest sample value>
+      --seed <seed>
+      --threads <count>
+      --output <image.png | .hdr | .pfm | .exr>
+      --paths <paths.obj>                   write the light paths of the samples below
+      --paths-pixels <x0>,<y0>,<x1>,<y1>    only the pixels from x0,y0 up to x1,y1
+      --paths-samples <first>,<end>         only the samples from first up to end
+  ray-tracer info <scene.toml | model.obj>
+  ray-tracer convert <scene.toml | model.obj> <output.obj | output.toml> [--scale <scale>]
+
+obj files are read in meters, `--scale` sets the scene units per obj unit when writing
+
+exit codes:
+  0  success
+  1  the input could not be loaded
+  2  invalid arguments
+  3  an output could not be written";
+
+/// why the program stopped, each maps to its own exit code
+enum Failure {
+    Load(String),
+    Usage(String),
+    Write(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Load(_) => 1,
+            Failure::Usage(_) => 2,
+            Failure::Write(_) => 3,
+        }
+    }
+}
+
+/// positional arguments and `--name value` pairs
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, Failure> {
+        let mut result = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| Failure::Usage(format!("`--{name}` needs a value")))?;
+                    result.options.push((name.to_string(), value));
+                }
+                None => result.positional.push(arg),
+            }
+        }
+        Ok(result)
+    }
+
+    /// fails on options the command does not know
+    fn check(&self, known: &[&str], positional: usize) -> Result<(), Failure> {
+        if let Some((name, _)) = self.options.iter().find(|(n, _)| !known.contains(&&n[..])) {
+            return Err(Failure::Usage(format!("unknown option `--{name}`")));
+        }
+        if self.positional.len() != positional {
+            return Err(Failure::Usage(format!(
+                "expected {positional} argument(s), got {}",
+                self.positional.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn get<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, Failure> {
+        let Some((_, value)) = self.options.iter().rev().find(|(n, _)| n == name) else {
+            return Ok(None);
+        };
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| Failure::Usage(format!("invalid value `{value}` for `--{name}`")))
+    }
+}
+
+fn is_obj(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("obj"))
+}
+
+/// a scene file, or one that places an obj file as it is
+fn load_input(path: &str) -> Result<(SceneFile, Scene), Failure> {
+    let file = if is_obj(path) {
+        SceneFile {
+            meshes: vec![MeshDesc {
+                name: "model".to_string(),
+                path: path.to_string(),
+                scale: PIXELS_PER_METER,
+                y_up: false,
+            }],
+            instances: vec![InstanceDesc {
+                object: "model".to_string(),
+                material: None,
+                transform: Default::default(),
+            }],
+            ..Default::default()
+        }
+    } else {
+        SceneFile::load(path).map_err(|e| Failure::Load(e.to_string()))?
+    };
+    file.render
+        .validate()
+        .map_err(|e| Failure::Load(format!("{path}: {e}")))?;
+    let scene = file
+        .build_scene()
+        .map_err(|e| Failure::Load(e.to_string()))?;
+    Ok((file, scene))
+}
+
+/// looks at the middle of the scene along +y, far enough back to see all of it
+fn frame(scene: &Scene, camera: &mut CameraDesc) {
+    let bounds = scene.bvh.bounds();
+    if !bounds.is_finite() {
+        return;
+    }
+    let center = bounds.centroid();
+    let radius = 0.5 * (&bounds.max - &bounds.min).len();
+    let distance = radius / (0.5 * camera.vfov).to_radians().sin();
+    camera.target = [center.x(), center.y(), center.z()];
+    camera.eye = [center.x(), center.y() - distance, center.z()];
+}
+
+fn render(args: &Args) -> Result<(), Failure> {
+    args.check(
+        &[
+            "resolution",
+            "spp",
+            "max-depth",
+            "integrator",
+            "clamp",
+            "seed",
+            "threads",
+            "output",
+            "paths",
+            "paths-pixels",
+            "paths-samples",
+        ],
+        1,
+    )?;
+    let input = &args.positional[0];
+    let (mut file, scene) = load_input(input)?;
+    if is_obj(input) {
+        frame(&scene, &mut file.camera);
+    }
+
+    if let Some(resolution) = args.get::<String>("resolution")? {
+        let parsed = resolution
+            .split_once('x')
+            .and_then(|(w, h)| Some([w.parse().ok()?, h.parse().ok()?]))
+            .filter(|[w, h]: &[usize; 2]| *w > 0 && *h > 0);
+        file.camera.resolution = parsed.ok_or_else(|| {
+            Failure::Usage(format!("invalid resolution `{resolution}`, expected WxH"))
+        })?;
+    }
+    let render = &mut file.render;
+    if let Some(spp) = args.get("spp")? {
+        render.spp = spp;
+    }
+    if let Some(max_depth) = args.get("max-depth")? {
+        render.max_depth = max_depth;
+    }
+    if let Some(integrator) = args.get("integrator")? {
+        render.integrator = integrator;
+    }
+    if let Some(clamp) = args.get("clamp")? {
+        render.clamp = Some(clamp);
+    }
+    if let Some(seed) = args.get("seed")? {
+        render.seed = seed;
+    }
+    if let Some(threads) = args.get("threads")? {
+        render.threads = Some(threads);
+    }
+    render
+        .validate()
+        .map_err(|e| Failure::Usage(e.to_string()))?;
+    if let Some(output) = args.get::<String>("output")? {
+        // relative to where the command runs, not to the scene file
+        let output = std::env::current_dir()
+            .map(|dir| dir.join(&output).to_string_lossy().into_owned())
+            .unwrap_or(output);
+        let extension = Path::new(&output)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        let mut outputs = OutputDesc {
+            exr_pixel_type: file.output.exr_pixel_type,
+            tone_mapping: file.output.tone_mapping,
+            ..Default::default()
+        };
+        match extension.as_deref() {
+            Some("png") => outputs.png = Some(output),
+            Some("hdr") => outputs.hdr = Some(output),
+            Some("pfm") => outputs.pfm = Some(output),
+            Some("exr") => outputs.exr = Some(output),
+            _ => {
+                return Err(Failure::Usage(format!(
+                    "cannot tell the image format of `{output}`"
+                )))
+            }
+        }
+        file.output = outputs;
+    }
+    if !file.output.has_images() {
+        return Err(Failure::Usage(
+            "nothing to write, pass --output".to_string(),
+        ));
+    }
+
+    let mut filter = PathFilter::default();
+    if let Some(pixels) = args.get::<String>("paths-pixels")? {
+        let [x0, y0, x1, y1] = numbers(&pixels, "paths-pixels")?;
+        filter.pixels = Some((
+            range(x0, x1, "paths-pixels")?,
+            range(y0, y1, "paths-pixels")?,
+        ));
+    }
+    if let Some(samples) = args.get::<String>("paths-samples")? {
+        let [first, end] = numbers(&samples, "paths-samples")?;
+        filter.samples = Some(range(first, end, "paths-samples")?);
+    }
+    let recorder = args
+        .get::<String>("paths")?
+        .map(|path| ObjPathRecorder::new(&path, filter));
+
+    let (width, height) = (file.camera.resolution[0], file.camera.resolution[1]);
+    let start = Instant::now();
+    let renderer = file
+        .renderer_for(scene)
+        .map_err(|e| Failure::Load(e.to_string()))?;
+    let image = match &recorder {
+        Some(recorder) => renderer
+            .render_recorded(recorder)
+            .map_err(|e| Failure::Write(format!("{}: {e}", recorder.path)))?,
+        None => renderer.render(),
+    };
+    eprintln!(
+        "rendered {width}x{height} at {} spp in {:.2}s",
+        file.render.spp,
+        start.elapsed().as_secs_f64()
+    );
+    file.write_outputs(&image)
+        .map_err(|e| Failure::Write(e.to_string()))
+}
+
+/// a comma separated list of exactly `N` numbers
+fn numbers<const N: usize>(value: &str, name: &str) -> Result<[usize; N], Failure> {
+    let parsed: Option<Vec<usize>> = value.split(',').map(|n| n.trim().parse().ok()).collect();
+    parsed
+        .and_then(|numbers| numbers.try_into().ok())
+        .ok_or_else(|| {
+            Failure::Usage(format!(
+                "invalid value `{value}` for `--{name}`, expected {N} numbers"
+            ))
+        })
+}
+
+/// `start..end`, which has to select at least one number
+fn range(start: usize, end: usize, name: &str) -> Result<Range<usize>, Failure> {
+    if start < end {
+        Ok(start..end)
+    } else {
+        Err(Failure::Usage(format!(
+            "`--{name}` selects nothing from {start} up to {end}"
+        )))
+    }
+}
+
+fn info(args: &Args) -> Result<(), Failure> {
+    args.check(&[], 1)?;
+    let (file, scene) = load_input(&args.positional[0])?;
+    let bounds = scene.bvh.bounds();
+    println!("vertices    {}", scene.vertices.len());
+    println!("triangles   {}", scene.triangles.len());
+    println!("materials   {}", scene.materials.len());
+    println!("textures    {}", scene.textures.len());
+    println!("prototypes  {}", scene.prototypes.len());
+    println!("instances   {}", scene.instances.len());
+    println!("lights      {}", scene.lights.len());
+    if bounds.is_finite() {
+        println!(
+            "bounds      [{}, {}, {}] to [{}, {}, {}]",
+            bounds.min.x(),
+            bounds.min.y(),
+            bounds.min.z(),
+            bounds.max.x(),
+            bounds.max.y(),
+            bounds.max.z()
+        );
+    }
+    if !is_obj(&args.positional[0]) {
+        let [width, height] = file.camera.resolution;
+        println!("resolution  {width}x{height}");
+        println!("spp         {}", file.render.spp);
+        println!("max depth   {}", file.render.max_depth);
+        println!("integrator  {}", file.render.integrator);
+    }
+    Ok(())
+}
+
+/// `path` as a scene file saved at `output` refers to it, relative if it is in the same
+/// folder or below and absolute otherwise
+fn relative_to_output(path: &str, output: &str) -> String {
+    let Ok(absolute) = std::fs::canonicalize(path) else {
+        return path.to_string();
+    };
+    let folder = Path::new(output)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::canonicalize(folder)
+        .ok()
+        .and_then(|folder| absolute.strip_prefix(folder).ok())
+        .unwrap_or(&absolute)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn convert(args: &Args) -> Result<(), Failure> {
+    args.check(&["scale"], 2)?;
+    let (input, output) = (&args.positional[0], &args.positional[1]);
+    let scale = args.get("scale")?;
+    if scale.is_some_and(|scale| scale <= 0.0) {
+        return Err(Failure::Usage("`--scale` has to be positive".to_string()));
+    }
+    // the meshes of a scene file keep their own scales
+    if scale.is_some() && !is_obj(input) && !is_obj(output) {
+        return Err(Failure::Usage(
+            "`--scale` only applies when reading or writing an obj file".to_string(),
+        ));
+    }
+    let (mut file, scene) = load_input(input)?;
+
+    if is_obj(output) {
+        let mut writer = ObjWriter::with_scale(scale.unwrap_or(PIXELS_PER_METER));
+        let skipped = writer.add_instances(&scene);
+        if skipped > 0 {
+            eprintln!(
+                "warning: {skipped} instances of analytic shapes are not written to {output}"
+            );
+        }
+        writer
+            .write(output)
+            .map_err(|e| Failure::Write(format!("{output}: {e}")))
+    } else if Path::new(output)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("toml"))
+    {
+        if is_obj(input) {
+            frame(&scene, &mut file.camera);
+            file.meshes[0].scale = scale.unwrap_or(PIXELS_PER_METER);
+            file.meshes[0].path = relative_to_output(input, output);
+        }
+        file.save(output).map_err(|e| Failure::Write(e.to_string()))
+    } else {
+        Err(Failure::Usage(format!(
+            "cannot tell the format of `{output}`, expected .obj or .toml"
+        )))
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    let result = Args::parse(args).and_then(|args| match command.as_deref() {
+        Some("render") => render(&args),
+        Some("info") => info(&args),
+        Some("convert") => convert(&args),
+        Some("help" | "-h" | "--help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(Failure::Usage(format!("unknown command `{command}`"))),
+        None => Err(Failure::Usage("missing command".to_string())),
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            match &failure {
+                Failure::Usage(message) => eprintln!("error: {message}\n\n{USAGE}"),
+                Failure::Load(message) | Failure::Write(message) => eprintln!("error: {message}"),
+            }
+            ExitCode::from(failure.exit_code())
+        }
+    }
 }
@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// everything that can go wrong while loading an obj file and the mtl files and textures
+/// it references, `line` numbers start at 1
+#[derive(Debug)]
+pub enum ObjError {
+    /// the file could not be read, or a texture could not be decoded
+    Io { file: String, error: std::io::Error },
+    /// a statement is missing one of its arguments
+    MissingArgument {
+        file: String,
+        line: usize,
+        keyword: String,
+    },
+    InvalidNumber {
+        file: String,
+        line: usize,
+        word: String,
+    },
+    /// a `v/vt/vn` triple in a face that is not made of valid indices
+    InvalidFaceElement {
+        file: String,
+        line: usize,
+        element: String,
+    },
+    /// an index that points past the elements defined so far, or is 0
+    IndexOutOfRange {
+        file: String,
+        line: usize,
+        index: i64,
+    },
+    /// a face with a number of vertices that cannot be loaded
+    UnsupportedFace {
+        file: String,
+        line: usize,
+        vertices: usize,
+    },
+    UnknownMaterial {
+        file: String,
+        line: usize,
+        name: String,
+    },
+    /// a material property before the first `newmtl`
+    NoMaterial {
+        file: String,
+        line: usize,
+        keyword: String,
+    },
+}
+
+impl ObjError {
+    pub fn file(&self) -> &str {
+        match self {
+            ObjError::Io { file, .. }
+            | ObjError::MissingArgument { file, .. }
+            | ObjError::InvalidNumber { file, .. }
+            | ObjError::InvalidFaceElement { file, .. }
+            | ObjError::IndexOutOfRange { file, .. }
+            | ObjError::UnsupportedFace { file, .. }
+            | ObjError::UnknownMaterial { file, .. }
+            | ObjError::NoMaterial { file, .. } => file,
+        }
+    }
+
+    /// None for errors that concern the whole file
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ObjError::Io { .. } => None,
+            ObjError::MissingArgument { line, .. }
+            | ObjError::InvalidNumber { line, .. }
+            | ObjError::InvalidFaceElement { line, .. }
+            | ObjError::IndexOutOfRange { line, .. }
+            | ObjError::UnsupportedFace { line, .. }
+            | ObjError::UnknownMaterial { line, .. }
+            | ObjError::NoMaterial { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file())?;
+        if let Some(line) = self.line() {
+            write!(f, ":{line}")?;
+        }
+        match self {
+            ObjError::Io { error, .. } => write!(f, ": {error}"),
+            ObjError::MissingArgument { keyword, .. } => {
+                write!(f, ": `{keyword}` is missing an argument")
+            }
+            ObjError::InvalidNumber { word, .. } => write!(f, ": `{word}` is not a valid number"),
+            ObjError::InvalidFaceElement { element, .. } => {
+                write!(f, ": `{element}` is not a valid face element")
+            }
+            ObjError::IndexOutOfRange { index, .. } => write!(f, ": index {index} is out of range"),
+            ObjError::UnsupportedFace { vertices, .. } => {
+                write!(f, ": faces with {vertices} vertices are not supported")
+            }
+            ObjError::UnknownMaterial { name, .. } => write!(f, ": unknown material `{name}`"),
+            ObjError::NoMaterial { keyword, .. } => {
+                write!(f, ": `{keyword}` comes before any `newmtl`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
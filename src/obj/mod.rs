@@ -1,6 +1,12 @@
+mod error;
 mod mtl;
+mod options;
 mod parse;
+mod tokenize;
+mod triangulate;
 mod write;
 
+pub use error::ObjError;
+pub use options::{LoadOptions, PIXELS_PER_METER};
 pub use parse::ObjParser;
 pub use write::ObjWriter;
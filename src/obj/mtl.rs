@@ -1,59 +1,209 @@
 use std::{collections::HashMap, fs::read_to_string};
 
-use super::parse::parse_vector;
-use crate::scene::mesh::Material;
+use super::{
+    tokenize::{tokenize, Line},
+    ObjError,
+};
+use crate::{
+    scene::mesh::Material,
+    texture::{Texture, WrapMode},
+};
 
 #[derive(Default, Debug, Clone)]
 pub struct MtlParser {
     pub materials: Vec<Material>,
     pub material_indices: HashMap<String, usize>,
+    pub textures: Vec<Texture>,
+    /// every file is only loaded once per color space and wrap mode
+    texture_indices: HashMap<(String, bool, WrapMode), usize>,
+    /// folder the mtl file is in, texture paths are relative to it
+    folder: String,
+}
+
+/// texture loaded by a `map_*` statement along with the options the material needs
+struct MapStatement {
+    texture: usize,
+    /// `-bm`, scales bump heights and normal map strength
+    bump_multiplier: f64,
+}
+
+/// number of arguments the texture map options take, `-o`, `-s` and `-t` take up to 3
+/// https://paulbourke.net/dataformats/mtl/
+fn option_arg_count(option: &str) -> Option<usize> {
+    match option {
+        "-blendu" | "-blendv" | "-boost" | "-cc" | "-clamp" | "-bm" | "-imfchan" | "-texres"
+        | "-type" | "-wrap" => Some(1),
+        "-mm" => Some(2),
+        "-o" | "-s" | "-t" => Some(3),
+        _ => None,
+    }
 }
 
 impl MtlParser {
-    pub fn parse(&mut self, path: &str) -> Result<Self, std::io::Error> {
-        let file_string = read_to_string(path)?;
-        let lines: Vec<Vec<&str>> = file_string
-            .lines()
-            .filter(|s| s.chars().next().unwrap_or('#') != '#')
-            .map(|s| s.split(' ').collect())
-            .collect();
-        for line in lines.iter() {
-            self.parse_line(line)
+    pub fn parse(&mut self, path: &str) -> Result<Self, ObjError> {
+        let file_string = read_to_string(path).map_err(|error| ObjError::Io {
+            file: path.to_string(),
+            error,
+        })?;
+        self.folder = match path.rfind('/') {
+            Some(i) => path[..=i].to_string(),
+            None => String::new(),
+        };
+        for line in tokenize(path, &file_string) {
+            self.parse_line(&line)?;
         }
         Ok(self.clone())
     }
-    fn parse_line(&mut self, line: &[&str]) {
-        match line[0] {
+
+    /// the material that is being defined
+    fn material(&mut self, line: &Line) -> Result<&mut Material, ObjError> {
+        self.materials
+            .last_mut()
+            .ok_or_else(|| ObjError::NoMaterial {
+                file: line.file.to_string(),
+                line: line.number,
+                keyword: line.keyword().to_string(),
+            })
+    }
+
+    fn parse_line(&mut self, line: &Line) -> Result<(), ObjError> {
+        match line.keyword() {
             "newmtl" => {
+                line.arg(0)?;
                 self.material_indices
-                    .insert(line[1].to_string(), self.materials.len());
+                    .insert(line.args().join(" "), self.materials.len());
                 self.materials.push(Material::default());
             }
             "Ke" => {
-                self.materials.last_mut().unwrap().emission = parse_vector(line);
+                self.material(line)?.emission = line.color()?;
             }
             "Kd" => {
-                self.materials.last_mut().unwrap().diffuse = parse_vector(line);
-            }
-            // "Ka" => {
-            //     self.materials.last_mut().unwrap().ambient = Self::parse_vector(line);
-            // }
-            // "Ks" => {
-            //     self.materials.last_mut().unwrap().specular = Self::parse_vector(line);
-            // }
-            // "Ns" => {
-            //     self.materials.last_mut().unwrap().specular_exp = line[1].parse::<f64>().unwrap();
-            // }
-            // "Ni" => {
-            //     self.materials.last_mut().unwrap().density = line[1].parse::<f64>().unwrap();
-            // }
-            // "d" => {
-            //     self.materials.last_mut().unwrap().opacity = line[1].parse::<f64>().unwrap();
-            // }
-            // "Tr" => {
-            //     self.materials.last_mut().unwrap().opacity = 1.0 - line[1].parse::<f64>().unwrap();
-            // }
+                self.material(line)?.diffuse = line.color()?;
+            }
+            "Ka" => {
+                self.material(line)?.ambient = line.color()?;
+            }
+            "Ks" => {
+                self.material(line)?.specular = line.color()?;
+            }
+            "Ns" => {
+                self.material(line)?.specular_exp = line.number(0)?;
+            }
+            "Tf" => {
+                self.material(line)?.filter = line.color()?;
+            }
+            "Ni" => {
+                self.material(line)?.density = line.number(0)?;
+            }
+            // the value comes last, after an optional `-halo`
+            "d" => {
+                let last = line.args().len().saturating_sub(1);
+                self.material(line)?.opacity = line.number(last)?;
+            }
+            "Tr" => {
+                self.material(line)?.opacity = 1.0 - line.number::<f64>(0)?;
+            }
+            "illum" => {
+                self.material(line)?.illum = line.number(0)?;
+            }
+            "map_Kd" => {
+                let map = self.parse_map(line, true)?;
+                self.material(line)?.diffuse_map = Some(map.texture);
+            }
+            "map_Ke" => {
+                let map = self.parse_map(line, true)?;
+                self.material(line)?.emission_map = Some(map.texture);
+            }
+            "map_Ks" => {
+                let map = self.parse_map(line, true)?;
+                self.material(line)?.specular_map = Some(map.texture);
+            }
+            "map_Ns" => {
+                let map = self.parse_map(line, false)?;
+                self.material(line)?.specular_exp_map = Some(map.texture);
+            }
+            // blender writes tangent space normal maps as `map_Bump`
+            "norm" | "map_Bump" | "map_bump" => {
+                let map = self.parse_map(line, false)?;
+                let material = self.material(line)?;
+                material.normal_map = Some(map.texture);
+                material.normal_strength = map.bump_multiplier;
+            }
+            "bump" => {
+                let map = self.parse_map(line, false)?;
+                let material = self.material(line)?;
+                material.bump_map = Some(map.texture);
+                material.bump_height = map.bump_multiplier;
+            }
             _ => {}
         }
+        Ok(())
+    }
+
+    /// loads the texture of a `map_*` statement, `-clamp on` and the nonstandard
+    /// `-wrap repeat|clamp|mirror` pick the wrap mode, `-bm` is kept and every other
+    /// option is skipped
+    fn parse_map(&mut self, line: &Line, srgb: bool) -> Result<MapStatement, ObjError> {
+        let mut words = line.args().iter().peekable();
+        let mut wrap = WrapMode::default();
+        let mut bump_multiplier = 1.0;
+        while let Some(count) = words.peek().and_then(|w| option_arg_count(w)) {
+            let option = *words.next().unwrap();
+            match option {
+                "-clamp" => {
+                    if words.next() == Some(&"on") {
+                        wrap = WrapMode::Clamp;
+                    }
+                }
+                "-bm" => {
+                    let word = words.next().copied().unwrap_or_default();
+                    bump_multiplier = line.parse(word)?;
+                }
+                "-wrap" => {
+                    wrap = match words.next() {
+                        Some(&"clamp") => WrapMode::Clamp,
+                        Some(&"mirror") => WrapMode::Mirror,
+                        _ => WrapMode::Repeat,
+                    };
+                }
+                _ => {
+                    for i in 0..count {
+                        // only the first of several numbers is required
+                        if i > 0 && words.peek().is_none_or(|w| w.parse::<f64>().is_err()) {
+                            break;
+                        }
+                        words.next();
+                    }
+                }
+            }
+        }
+        let name = words.copied().collect::<Vec<&str>>().join(" ");
+        if name.is_empty() {
+            return Err(ObjError::MissingArgument {
+                file: line.file.to_string(),
+                line: line.number,
+                keyword: line.keyword().to_string(),
+            });
+        }
+        let path = format!("{}{}", self.folder, name);
+
+        let key = (path, srgb, wrap);
+        let texture = match self.texture_indices.get(&key) {
+            Some(&index) => index,
+            None => {
+                let texture =
+                    Texture::from_png(&key.0, srgb, wrap).map_err(|error| ObjError::Io {
+                        file: key.0.clone(),
+                        error,
+                    })?;
+                self.textures.push(texture);
+                self.texture_indices.insert(key, self.textures.len() - 1);
+                self.textures.len() - 1
+            }
+        };
+        Ok(MapStatement {
+            texture,
+            bump_multiplier,
+        })
     }
 }
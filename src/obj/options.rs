@@ -0,0 +1,50 @@
+use crate::vector::{Matrix4, Vector3};
+
+/// scene units per obj unit by default, obj files are in meters and scenes in centimeters
+pub const PIXELS_PER_METER: f64 = 100.0;
+
+/// how the coordinates of an obj file are brought into the scene. vertices are scaled,
+/// then turned to z up, then transformed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadOptions {
+    pub scale: f64,
+    /// most dcc tools export with y up, this rotates y onto z
+    pub y_up: bool,
+    /// affine transform, may be singular or mirror the mesh
+    pub transform: Matrix4<f64>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            scale: PIXELS_PER_METER,
+            y_up: false,
+            transform: Matrix4::identity(),
+        }
+    }
+}
+
+impl LoadOptions {
+    /// the whole conversion as a single matrix
+    pub fn matrix(&self) -> Matrix4<f64> {
+        let scale = Matrix4::scaling(&Vector3::new(self.scale, self.scale, self.scale));
+        // +90 degrees around x, (x, y, z) -> (x, -z, y), written out so it stays exact
+        let axis = if self.y_up {
+            Matrix4::new([
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, -1.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        } else {
+            Matrix4::identity()
+        };
+        self.transform * axis * scale
+    }
+
+    /// how much lengths grow on average, for values like bump heights that are given in
+    /// obj units
+    pub fn length_scale(&self) -> f64 {
+        self.matrix().determinant().abs().cbrt()
+    }
+}
@@ -1,27 +1,19 @@
-use std::{collections::HashMap, fs::read_to_string, str::FromStr};
+use std::{collections::HashMap, fs::read_to_string};
 
-use super::mtl::MtlParser;
+use super::{
+    mtl::MtlParser,
+    options::LoadOptions,
+    tokenize::{tokenize, Line},
+    triangulate::triangulate,
+    ObjError,
+};
 use crate::{
     scene::mesh::{Material, Triangle},
-    vector::Vector3,
+    texture::Texture,
+    vector::{Matrix4, Vector3},
 };
 
-const PIXELS_PER_METER: f64 = 100.0;
-
-pub fn parse_vector(line: &[&str]) -> Vector3<f64> {
-    let mut words = line.iter();
-    words.next().unwrap();
-    Vector3::new(
-        words.next().unwrap().parse::<f64>().unwrap(),
-        words.next().unwrap().parse::<f64>().unwrap(),
-        words.next().unwrap().parse::<f64>().unwrap(),
-    )
-}
-
-fn collect_array<T, const N: usize>(mut i: impl Iterator<Item = T>) -> [T; N] {
-    std::array::from_fn(|_| i.next().unwrap())
-}
-
+/// indices of one corner of a face, already 0 based
 pub struct FaceEntryElement {
     pub vertex: usize,
     pub texture: Option<usize>,
@@ -29,12 +21,48 @@ pub struct FaceEntryElement {
 }
 
 impl FaceEntryElement {
-    pub fn new(s: &str) -> Result<FaceEntryElement, <usize as FromStr>::Err> {
-        let w: Vec<&str> = s.split('/').collect();
+    /// parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, `counts` are the number of vertices,
+    /// texture coordinates and normals defined so far. negative indices count backwards
+    /// from the last one, -1 being the most recent
+    pub fn new(line: &Line, s: &str, counts: [usize; 3]) -> Result<FaceEntryElement, ObjError> {
+        let mut indices = [None; 3];
+        let mut parts = s.split('/');
+        for (i, index) in indices.iter_mut().enumerate() {
+            let Some(part) = parts.next() else {
+                break;
+            };
+            if part.is_empty() && i > 0 {
+                continue;
+            }
+            let value = part
+                .parse::<i64>()
+                .map_err(|_| ObjError::InvalidFaceElement {
+                    file: line.file.to_string(),
+                    line: line.number,
+                    element: s.to_string(),
+                })?;
+            let resolved = if value < 0 {
+                counts[i] as i64 + value
+            } else {
+                value - 1
+            };
+            if value == 0 || resolved < 0 || resolved >= counts[i] as i64 {
+                return Err(line.error_index(value));
+            }
+            *index = Some(resolved as usize);
+        }
+        if parts.next().is_some() {
+            return Err(ObjError::InvalidFaceElement {
+                file: line.file.to_string(),
+                line: line.number,
+                element: s.to_string(),
+            });
+        }
+        let [vertex, texture, normal] = indices;
         Ok(FaceEntryElement {
-            vertex: w[0].parse::<usize>()?,
-            texture: w[1].parse::<usize>().ok(),
-            normal: w[2].parse::<usize>().ok(),
+            vertex: vertex.unwrap(),
+            texture,
+            normal,
         })
     }
 }
@@ -44,105 +72,283 @@ pub struct ObjParser {
     pub point_normals: Vec<Vector3<f64>>,
     pub face_normals: Vec<Vector3<f64>>,
     pub vertices: Vec<Vector3<f64>>,
+    pub texture_coords: Vec<(f64, f64)>,
+    pub tangents: Vec<Vector3<f64>>,
     pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
     pub triangles: Vec<Triangle>,
     pub meshes: Vec<usize>,
+    options: LoadOptions,
+    /// `options` as one matrix, and the matrix for normals
+    transform: Matrix4<f64>,
+    normal_transform: Matrix4<f64>,
+    /// `transform` turns meshes inside out, faces are wound the other way to undo it
+    mirrors: bool,
     material_indices: HashMap<String, usize>,
-    cur_material: usize,
+    cur_material: Option<usize>,
+    /// gray material for faces that come before any `usemtl`
+    default_material: Option<usize>,
+    /// current `s` group, 0 when smoothing is off
+    smoothing_group: u32,
+    /// triangles without vertex normals that get smooth ones once every face is known,
+    /// along with their smoothing group
+    smoothed: Vec<(usize, u32)>,
 }
 
 impl ObjParser {
-    pub fn parse(&mut self, path: &str) -> Result<(), std::io::Error> {
-        let file_string = read_to_string(path)?;
-        let lines: Vec<Vec<&str>> = file_string
-            .lines()
-            .filter(|s| s.chars().next().unwrap_or('#') != '#')
-            .map(|s| s.split(' ').collect())
-            .collect();
-        for mut line in lines {
-            self.parse_line(&mut line, path)?;
+    pub fn with_options(options: LoadOptions) -> ObjParser {
+        let transform = options.matrix();
+        // like `Transform::normal`, a singular matrix has no inverse but its cofactors
+        // still keep normals perpendicular
+        let normal_transform = transform
+            .inverse()
+            .map_or_else(|| transform.cofactor(), |inverse| inverse.transpose());
+        ObjParser {
+            options,
+            transform,
+            normal_transform,
+            mirrors: transform.determinant() < 0.0,
+            ..Default::default()
         }
+    }
+
+    pub fn parse(&mut self, path: &str) -> Result<(), ObjError> {
+        let file_string = read_to_string(path).map_err(|error| ObjError::Io {
+            file: path.to_string(),
+            error,
+        })?;
+        for line in tokenize(path, &file_string) {
+            self.parse_line(&line)?;
+        }
+        self.calculate_smooth_normals();
+        self.calculate_tangents();
         Ok(())
     }
 
-    fn parse_line(&mut self, line: &mut Vec<&str>, path: &str) -> Result<(), std::io::Error> {
-        match line[0] {
+    fn parse_line(&mut self, line: &Line) -> Result<(), ObjError> {
+        match line.keyword() {
             "mtllib" => {
-                let mut mtl_name: String = path.to_string();
-                // get folder
-                while !mtl_name.is_empty() && mtl_name.pop().unwrap() != '/' {}
-                if !mtl_name.is_empty() {
-                    mtl_name.push('/');
+                line.arg(0)?;
+                // relative to the folder of the obj file
+                let folder = match line.file.rfind('/') {
+                    Some(i) => &line.file[..=i],
+                    None => "",
+                };
+                for name in line.args() {
+                    self.load_mtl(&format!("{folder}{name}"))?;
                 }
-                mtl_name.push_str(line[1]);
-
-                let MtlParser {
-                    materials,
-                    material_indices,
-                    ..
-                } = MtlParser::default().parse(&mtl_name)?;
-                self.materials = materials;
-                self.material_indices = material_indices;
             }
             "o" => self.meshes.push(self.triangles.len()),
+            "s" => {
+                self.smoothing_group = match line.arg(0)? {
+                    "off" => 0,
+                    "on" => 1,
+                    group => line.parse(group)?,
+                };
+            }
             "f" => {
-                assert_eq!(
-                    4,
-                    line.len(),
-                    "this face is not a triangle, please triangulate the surface before parsing"
-                );
-                let triangle = self.parse_face(line).expect("normals not provided");
-                self.triangles.push(triangle);
+                let triangles = self.parse_face(line)?;
+                self.triangles.extend(triangles);
             }
             "vn" => {
-                let mut normal = parse_vector(line) * PIXELS_PER_METER;
+                let mut normal = self.normal_transform.transform_direction(&line.vector()?);
                 normal.normalize();
                 self.point_normals.push(normal);
             }
+            "vt" => {
+                // the optional w coordinate is only used by 3d textures
+                let u = line.number(0)?;
+                let v = match line.args().get(1) {
+                    Some(v) => line.parse(v)?,
+                    None => 0.0,
+                };
+                self.texture_coords.push((u, v));
+            }
             "v" => {
-                self.vertices.push(parse_vector(line) * PIXELS_PER_METER);
+                let vertex = self.transform.transform_point(&line.vector()?);
+                self.vertices.push(vertex);
             }
             "usemtl" => {
-                self.cur_material = *self
-                    .material_indices
-                    .get(line[1])
-                    .expect("invalid material");
+                line.arg(0)?;
+                let name = line.args().join(" ");
+                let Some(&material) = self.material_indices.get(&name) else {
+                    return Err(ObjError::UnknownMaterial {
+                        file: line.file.to_string(),
+                        line: line.number,
+                        name,
+                    });
+                };
+                self.cur_material = Some(material);
             }
             _ => {}
         }
         Ok(())
     }
 
-    fn parse_face(&mut self, line: &[&str]) -> Option<Triangle> {
-        assert_eq!(
-            line.len(),
-            4,
-            "please triangulate the obj file, then try again"
-        );
-        let parsed: Vec<FaceEntryElement> = line
+    /// appends the materials and textures of a library, later ones win on name clashes
+    fn load_mtl(&mut self, path: &str) -> Result<(), ObjError> {
+        let MtlParser {
+            materials,
+            material_indices,
+            textures,
+            ..
+        } = MtlParser::default().parse(path)?;
+        let (material_offset, texture_offset) = (self.materials.len(), self.textures.len());
+        for mut material in materials {
+            material.offset_textures(texture_offset);
+            // bump heights are in the units of the obj file, normal maps have none
+            material.bump_height *= self.options.length_scale();
+            self.materials.push(material);
+        }
+        for (name, index) in material_indices {
+            self.material_indices.insert(name, index + material_offset);
+        }
+        self.textures.extend(textures);
+        Ok(())
+    }
+
+    fn material(&mut self) -> usize {
+        if let Some(material) = self.cur_material {
+            return material;
+        }
+        *self.default_material.get_or_insert_with(|| {
+            self.materials.push(Material {
+                diffuse: Vector3::new(0.8, 0.8, 0.8),
+                ..Default::default()
+            });
+            self.materials.len() - 1
+        })
+    }
+
+    /// polygons with more than three corners are split into several triangles
+    fn parse_face(&mut self, line: &Line) -> Result<Vec<Triangle>, ObjError> {
+        if line.args().len() < 3 {
+            return Err(ObjError::UnsupportedFace {
+                file: line.file.to_string(),
+                line: line.number,
+                vertices: line.args().len(),
+            });
+        }
+        let counts = [
+            self.vertices.len(),
+            self.texture_coords.len(),
+            self.point_normals.len(),
+        ];
+        let parsed = line
+            .args()
+            .iter()
+            .map(|s| FaceEntryElement::new(line, s, counts))
+            .collect::<Result<Vec<FaceEntryElement>, ObjError>>()?;
+        let has_normals = parsed.iter().all(|p| p.normal.is_some());
+        let has_texture_coords = parsed.iter().all(|p| p.texture.is_some());
+        let material = self.material();
+
+        let corners: Vec<Vector3<f64>> = parsed
             .iter()
-            .skip(1)
-            .map(|s| FaceEntryElement::new(s).expect("invalid face element"))
+            .map(|p| self.vertices[p.vertex].clone())
             .collect();
-        for p in parsed.iter() {
-            if p.texture.is_some() {
-                println!("texture not currently supported");
-                break;
+        let mut triangles = Vec::with_capacity(parsed.len() - 2);
+        for [a, mut b, mut c] in triangulate(&corners) {
+            if self.mirrors {
+                std::mem::swap(&mut b, &mut c);
+            }
+            let (a, b, c) = (&parsed[a], &parsed[b], &parsed[c]);
+            let mut triangle = Triangle {
+                vertices: [a.vertex, b.vertex, c.vertex],
+                point_normals: [a.normal, b.normal, c.normal].map(Option::unwrap_or_default),
+                texture_coords: has_texture_coords
+                    .then(|| [a.texture.unwrap(), b.texture.unwrap(), c.texture.unwrap()]),
+                tangents: None,
+                face_normal: self.face_normals.len(),
+                material,
+            };
+            let face_normal = self.calculate_face_normal(&triangle, true);
+            if !has_normals && self.smoothing_group != 0 {
+                self.smoothed
+                    .push((self.triangles.len() + triangles.len(), self.smoothing_group));
+            } else if !has_normals {
+                // flat shaded, every corner uses the face normal
+                triangle.point_normals = [self.point_normals.len(); 3];
+                self.point_normals.push(face_normal.clone());
+            }
+            self.face_normals.push(face_normal);
+            triangles.push(triangle);
+        }
+        Ok(triangles)
+    }
+
+    /// normals for the corners of `smoothed` triangles, averaged over the faces of the same
+    /// smoothing group around each vertex and weighted by their area
+    fn calculate_smooth_normals(&mut self) {
+        let mut normal_indices: HashMap<(usize, u32), usize> = HashMap::new();
+        let smoothed = std::mem::take(&mut self.smoothed);
+        for &(triangle, group) in smoothed.iter() {
+            let [v0, v1, v2] = self.triangles[triangle].vertices.map(|v| &self.vertices[v]);
+            // the face normal scaled by twice the area
+            let weighted = (v2 - v0).cross(&(v1 - v0));
+            for i in 0..3 {
+                let key = (self.triangles[triangle].vertices[i], group);
+                let index = *normal_indices.entry(key).or_insert_with(|| {
+                    self.point_normals.push(Vector3::default());
+                    self.point_normals.len() - 1
+                });
+                self.point_normals[index] += &weighted;
+                self.triangles[triangle].point_normals[i] = index;
+            }
+        }
+        for &index in normal_indices.values() {
+            let normal = &mut self.point_normals[index];
+            if normal.len() > 0.0 {
+                normal.normalize();
+            }
+        }
+    }
+
+    /// per vertex tangents along increasing u, averaged over the faces that share a vertex,
+    /// normal and texture coordinate so uv seams stay sharp
+    /// https://terathon.com/blog/tangent-space.html
+    fn calculate_tangents(&mut self) {
+        let mut tangent_indices: HashMap<(usize, usize, usize), usize> = HashMap::new();
+        for triangle in self.triangles.iter_mut() {
+            let Some(uvs) = triangle.texture_coords else {
+                continue;
+            };
+            let [p0, p1, p2] = triangle.vertices.map(|v| &self.vertices[v]);
+            let [t0, t1, t2] = uvs.map(|t| self.texture_coords[t]);
+            let (e0, e1) = (p1 - p0, p2 - p0);
+            let (du0, dv0) = (t1.0 - t0.0, t1.1 - t0.1);
+            let (du1, dv1) = (t2.0 - t0.0, t2.1 - t0.1);
+            let det = du0 * dv1 - du1 * dv0;
+            // degenerate uvs still get a tangent slot, it just stays zero
+            let tangent = if det.abs() > f64::EPSILON {
+                (&e0 * dv1 - &e1 * dv0) * (1.0 / det)
+            } else {
+                Vector3::default()
+            };
+            // weighted by area, the cross product is twice that
+            let area = e1.cross(&e0).len();
+            let tangent = if tangent.len() > 0.0 {
+                tangent.normalized() * area
+            } else {
+                tangent
+            };
+
+            let mut indices = [0; 3];
+            for (i, index) in indices.iter_mut().enumerate() {
+                let key = (triangle.vertices[i], triangle.point_normals[i], uvs[i]);
+                *index = *tangent_indices.entry(key).or_insert_with(|| {
+                    self.tangents.push(Vector3::default());
+                    self.tangents.len() - 1
+                });
+                self.tangents[*index] += &tangent;
+            }
+            triangle.tangents = Some(indices);
+        }
+        for tangent in self.tangents.iter_mut() {
+            if tangent.len() > 0.0 {
+                tangent.normalize();
             }
         }
-        let triangle = Triangle {
-            vertices: collect_array(parsed.iter().map(|x| x.vertex - 1)),
-            point_normals: [
-                parsed[0].normal? - 1,
-                parsed[1].normal? - 1,
-                parsed[2].normal? - 1,
-            ],
-            face_normal: self.face_normals.len(),
-            material: self.cur_material,
-        };
-        self.face_normals
-            .push(self.calculate_face_normal(&triangle, true));
-        Some(triangle)
     }
 
     fn calculate_face_normal(&self, triangle: &Triangle, clockwise: bool) -> Vector3<f64> {
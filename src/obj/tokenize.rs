@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use super::ObjError;
+use crate::vector::Vector3;
+
+/// one statement of an obj or mtl file split into words, never empty
+pub struct Line<'a> {
+    pub file: &'a str,
+    /// where the statement starts
+    pub number: usize,
+    pub words: Vec<&'a str>,
+}
+
+/// splits statements on any whitespace, drops comments and blank lines, and joins lines
+/// that end in a `\` with the next one
+pub fn tokenize<'a>(file: &'a str, text: &'a str) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    let mut words = Vec::new();
+    let mut start = 0;
+    for (i, line) in text.lines().enumerate() {
+        if words.is_empty() {
+            start = i + 1;
+        }
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let line = line.trim_end();
+        let (line, continued) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        words.extend(line.split_whitespace());
+        if !continued && !words.is_empty() {
+            lines.push(Line {
+                file,
+                number: start,
+                words: std::mem::take(&mut words),
+            });
+        }
+    }
+    if !words.is_empty() {
+        lines.push(Line {
+            file,
+            number: start,
+            words,
+        });
+    }
+    lines
+}
+
+impl<'a> Line<'a> {
+    pub fn keyword(&self) -> &'a str {
+        self.words[0]
+    }
+
+    /// everything after the keyword
+    pub fn args(&self) -> &[&'a str] {
+        &self.words[1..]
+    }
+
+    pub fn arg(&self, i: usize) -> Result<&'a str, ObjError> {
+        self.args()
+            .get(i)
+            .copied()
+            .ok_or_else(|| ObjError::MissingArgument {
+                file: self.file.to_string(),
+                line: self.number,
+                keyword: self.keyword().to_string(),
+            })
+    }
+
+    pub fn parse<T: FromStr>(&self, word: &str) -> Result<T, ObjError> {
+        word.parse::<T>().map_err(|_| ObjError::InvalidNumber {
+            file: self.file.to_string(),
+            line: self.number,
+            word: word.to_string(),
+        })
+    }
+
+    pub fn number<T: FromStr>(&self, i: usize) -> Result<T, ObjError> {
+        self.parse(self.arg(i)?)
+    }
+
+    /// the first three arguments, any further ones like a `w` are ignored
+    pub fn vector(&self) -> Result<Vector3<f64>, ObjError> {
+        Ok(Vector3::new(
+            self.number(0)?,
+            self.number(1)?,
+            self.number(2)?,
+        ))
+    }
+
+    /// an rgb triple, a single value is used for all three channels
+    pub fn color(&self) -> Result<Vector3<f64>, ObjError> {
+        let r = self.number(0)?;
+        if self.args().len() < 3 {
+            return Ok(Vector3::new(r, r, r));
+        }
+        Ok(Vector3::new(r, self.number(1)?, self.number(2)?))
+    }
+
+    pub fn error_index(&self, index: i64) -> ObjError {
+        ObjError::IndexOutOfRange {
+            file: self.file.to_string(),
+            line: self.number,
+            index,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::vector::Vector3;
+
+/// splits a planar polygon into triangles with the same winding, as indices into `points`.
+/// convex polygons become a fan, concave ones are ear clipped
+/// https://www.geometrictools.com/Documentation/TriangulationByEarClipping.pdf
+pub fn triangulate(points: &[Vector3<f64>]) -> Vec<[usize; 3]> {
+    let fan = |indices: &[usize]| -> Vec<[usize; 3]> {
+        (1..indices.len().saturating_sub(1))
+            .map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect()
+    };
+    let all: Vec<usize> = (0..points.len()).collect();
+    if points.len() <= 3 {
+        return fan(&all);
+    }
+
+    // newell's method gives a robust normal for the whole polygon
+    let mut normal = Vector3::<f64>::default();
+    for (i, p) in points.iter().enumerate() {
+        let q = &points[(i + 1) % points.len()];
+        normal += Vector3::new(
+            (p.y() - q.y()) * (p.z() + q.z()),
+            (p.z() - q.z()) * (p.x() + q.x()),
+            (p.x() - q.x()) * (p.y() + q.y()),
+        );
+    }
+    // project onto the plane the polygon is most parallel to, flipping one axis so the
+    // polygon always winds counter clockwise in 2d
+    let axis = (0..3)
+        .max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs()))
+        .unwrap();
+    if normal[axis].abs() < f64::EPSILON {
+        return fan(&all);
+    }
+    let sign = normal[axis].signum();
+    let flat: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| (p[(axis + 1) % 3], sign * p[(axis + 2) % 3]))
+        .collect();
+    let turn = |a: usize, b: usize, c: usize| {
+        let (a, b, c) = (flat[a], flat[b], flat[c]);
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    };
+
+    let n = points.len();
+    if (0..n).all(|i| turn((i + n - 1) % n, i, (i + 1) % n) >= 0.0) {
+        return fan(&all);
+    }
+
+    let mut remaining = all;
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+            if turn(a, b, c) <= 0.0 {
+                return false;
+            }
+            // no other corner may lie inside or on the candidate
+            remaining.iter().all(|&p| {
+                p == a
+                    || p == b
+                    || p == c
+                    || flat[p] == flat[a]
+                    || flat[p] == flat[b]
+                    || flat[p] == flat[c]
+                    || turn(a, b, p) < 0.0
+                    || turn(b, c, p) < 0.0
+                    || turn(c, a, p) < 0.0
+            })
+        });
+        let Some(i) = ear else {
+            // self intersecting or degenerate, a fan is as good as anything
+            triangles.extend(fan(&remaining));
+            return triangles;
+        };
+        triangles.push([
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
@@ -1,15 +1,23 @@
-use std::{fs::File, io::Write};
+use std::{collections::HashMap, fs::File, io::Write};
 
-use crate::scene::{mesh::Triangle, Scene};
-use crate::{ray::Ray, vector::Vector3};
+use super::PIXELS_PER_METER;
+use crate::scene::{instance::Geometry, Scene};
+use crate::vector::Vector3;
 
-const PIXELS_PER_METER: f64 = 100.0;
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ObjWriter {
+    /// scene units per obj unit, coordinates are divided by it on the way out
+    scale: f64,
     vertices: Vec<String>,
     normals: Vec<String>,
-    faces: Vec<String>,
-    lines: Vec<String>,
+    // 0 based (vertex, normal) indices
+    faces: Vec<[(usize, usize); 3]>,
+}
+
+impl Default for ObjWriter {
+    fn default() -> ObjWriter {
+        ObjWriter::with_scale(PIXELS_PER_METER)
+    }
 }
 
 impl ObjWriter {
@@ -17,6 +25,15 @@ impl ObjWriter {
         Self::default()
     }
 
+    pub fn with_scale(scale: f64) -> ObjWriter {
+        ObjWriter {
+            scale,
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            faces: Vec::new(),
+        }
+    }
+
     pub fn write(&self, path: &str) -> Result<(), std::io::Error> {
         let mut text = String::new();
         for v in self.vertices.iter() {
@@ -27,12 +44,8 @@ impl ObjWriter {
             text.push_str(&format!("vn {}", n));
             text.push('\n');
         }
-        for l in self.lines.iter() {
-            text.push_str(&format!("l {}", l));
-            text.push('\n');
-        }
         for f in self.faces.iter() {
-            text.push_str(&format!("f {}", f));
+            text.push_str(&format!("f {}", Self::write_triangle(f)));
             text.push('\n');
         }
 
@@ -48,27 +61,12 @@ impl ObjWriter {
         self.vertices.push(self.write_vector(v));
     }
     pub fn add_normal(&mut self, n: &Vector3<f64>) {
-        self.normals.push(self.write_vector(n));
-    }
-    pub fn add_triangle(&mut self, t: &Triangle) {
-        self.faces.push(self.write_triangle(t));
+        self.normals.push(format!("{} {} {}", n[0], n[1], n[2]));
     }
-    pub fn add_ray(&mut self, r: &Ray, t: f64) {
-        self.add_vertex(r.orig());
-        self.add_vertex(&(r.orig() + r.dir() * t));
-        let num_vertices = self.vertices.len();
-        let text = format!("{} {}", num_vertices + 1, num_vertices + 2);
-        self.lines.push(text);
-    }
-
-    fn write_triangle(&self, t: &Triangle) -> String {
+    fn write_triangle(t: &[(usize, usize); 3]) -> String {
         let mut text = String::new();
-        for i in 0..3 {
-            text.push_str(&format!(
-                "{}//{} ",
-                t.vertices[i] + 1,
-                t.point_normals[i] + 1
-            ));
+        for (v, n) in t {
+            text.push_str(&format!("{}//{} ", v + 1, n + 1));
         }
         text
     }
@@ -76,33 +74,44 @@ impl ObjWriter {
     fn write_vector(&self, v: &Vector3<f64>) -> String {
         format!(
             "{} {} {}",
-            v[0] / PIXELS_PER_METER,
-            v[1] / PIXELS_PER_METER,
-            v[2] / PIXELS_PER_METER
+            v[0] / self.scale,
+            v[1] / self.scale,
+            v[2] / self.scale
         )
     }
 
-    pub fn add_scene(&mut self, scene: &Scene, face_normals: bool) {
-        for v in scene.vertices.iter() {
-            self.add_vertex(v);
-        }
-        for n in scene.point_normals.iter() {
-            self.add_normal(n);
-        }
-        for t in scene.triangles.iter() {
-            self.add_triangle(t);
-        }
-        if face_normals {
-            for triangle in scene.triangles.iter() {
-                let middleish = triangle
-                    .vertices
-                    .iter()
-                    .map(|i| &scene.vertices[*i])
-                    .fold(Vector3::<f64>::default(), |acc, elem| acc + elem)
-                    * (1.0 / 3.0);
-                let r = Ray::new(middleish, scene.face_normals[triangle.face_normal].clone());
-                self.add_ray(&r, 100.0);
+    /// every mesh instance with its transform applied. analytic shapes are left out,
+    /// returns how many instances of them there were
+    pub fn add_instances(&mut self, scene: &Scene) -> usize {
+        let mut skipped = 0;
+        for instance in &scene.instances {
+            let Geometry::Mesh(triangles) = &scene.prototypes[instance.prototype].geometry else {
+                skipped += 1;
+                continue;
+            };
+            // scene index to index in `self`, every instance gets its own copies
+            let mut vertices = HashMap::new();
+            let mut normals = HashMap::new();
+            for t in &scene.triangles[triangles.clone()] {
+                let mut face = [(0, 0); 3];
+                for (i, corner) in face.iter_mut().enumerate() {
+                    let v = *vertices.entry(t.vertices[i]).or_insert_with(|| {
+                        let p = instance.transform.point(&scene.vertices[t.vertices[i]]);
+                        self.add_vertex(&p);
+                        self.vertices.len() - 1
+                    });
+                    let n = *normals.entry(t.point_normals[i]).or_insert_with(|| {
+                        let n = instance
+                            .transform
+                            .normal(&scene.point_normals[t.point_normals[i]]);
+                        self.add_normal(&n.normalized());
+                        self.normals.len() - 1
+                    });
+                    *corner = (v, n);
+                }
+                self.faces.push(face);
             }
         }
+        skipped
     }
 }
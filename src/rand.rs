@@ -1,10 +1,17 @@
-use std::{f64::consts::TAU, num::Wrapping};
+use std::{
+    f64::consts::{FRAC_PI_2, FRAC_PI_4, TAU},
+    num::Wrapping,
+};
 
-const MAX: f64 = std::u64::MAX as f64;
+const MAX: f64 = u64::MAX as f64;
 
-#[inline]
-fn rotl(x: u64, k: i32) -> u64 {
-    (x << k) | (x >> (64 - k))
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
 
 #[derive(Debug)]
@@ -15,8 +22,8 @@ impl UniformDist {
         let mut s1 = Wrapping(self.0[1]);
         let result = s0 + s1;
         s1 ^= s0;
-        self.0[0] = (Wrapping(rotl(s0.0, 55)) ^ s1 ^ (s1 << 14)).0;
-        self.0[1] = rotl(s1.0, 36);
+        self.0[0] = (Wrapping(s0.0.rotate_left(55)) ^ s1 ^ (s1 << 14)).0;
+        self.0[1] = s1.0.rotate_left(36);
         result.0
     }
 
@@ -27,7 +34,6 @@ impl UniformDist {
 
 #[derive(Debug)]
 pub struct NormalDist {
-    u: Option<(f64, f64)>,
     uniform: UniformDist,
 }
 
@@ -35,14 +41,53 @@ impl NormalDist {
     pub fn normal(&mut self) -> f64 {
         let u0 = self.uniform.next() as f64 / MAX;
         let u1 = self.uniform.next() as f64 / MAX;
-        let got = (-2.0 * u0.ln()).sqrt() * (TAU * u1).cos();
-        got
+        (-2.0 * u0.ln()).sqrt() * (TAU * u1).cos()
+    }
+
+    /// uniform sample in [0, 1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.uniform.next() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// uniform point on the unit disk
+    /// https://www.pbr-book.org/4ed/Sampling_Algorithms/Sampling_Multidimensional_Functions#ConcentricMapping
+    pub fn disk(&mut self) -> (f64, f64) {
+        let x = 2.0 * self.uniform() - 1.0;
+        let y = 2.0 * self.uniform() - 1.0;
+        if x == 0.0 && y == 0.0 {
+            return (0.0, 0.0);
+        }
+        let (r, theta) = if x.abs() > y.abs() {
+            (x, FRAC_PI_4 * (y / x))
+        } else {
+            (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+        };
+        (r * theta.cos(), r * theta.sin())
+    }
+
+    /// uniform point inside a regular polygon inscribed in the unit circle, with one
+    /// corner at `rotation` radians
+    pub fn polygon(&mut self, sides: u32, rotation: f64) -> (f64, f64) {
+        // every wedge between the center and an edge has the same area
+        let wedge = ((self.uniform() * sides as f64) as u32).min(sides - 1);
+        let a0 = rotation + TAU * wedge as f64 / sides as f64;
+        let a1 = rotation + TAU * (wedge + 1) as f64 / sides as f64;
+        let r0 = self.uniform().sqrt();
+        let r1 = self.uniform();
+        let (b0, b1) = (r0 * (1.0 - r1), r0 * r1);
+        (b0 * a0.cos() + b1 * a1.cos(), b0 * a0.sin() + b1 * a1.sin())
     }
 
     pub fn new(seed: [u64; 2]) -> NormalDist {
         Self {
-            u: None,
             uniform: UniformDist::new([seed[0], seed[1]]),
         }
     }
+
+    /// independent generator for one stream of `seed`, so work can be split up without the
+    /// result depending on how it gets scheduled
+    pub fn from_stream(seed: u64, mut stream: u64) -> NormalDist {
+        let mut state = seed ^ splitmix64(&mut stream);
+        NormalDist::new([splitmix64(&mut state), splitmix64(&mut state)])
+    }
 }
@@ -1,6 +1,6 @@
-use crate::{rand::NormalDist, vector::Vector3};
+use crate::vector::Vector3;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Ray {
     orig: Vector3<f64>,
     dir: Vector3<f64>,
@@ -19,14 +19,6 @@ impl Ray {
         &self.orig
     }
 
-    pub fn rand_dir(random_state: &mut NormalDist) -> Vector3<f64> {
-        Vector3::new(
-            random_state.normal(),
-            random_state.normal(),
-            random_state.normal(),
-        )
-    }
-
     pub fn point_at(&self, t: f64) -> Vector3<f64> {
         &self.orig + &self.dir * t
     }
@@ -0,0 +1,121 @@
+use std::{fmt::Write as _, ops::Range};
+
+use crate::{obj::PIXELS_PER_METER, vector::Vector3};
+
+/// a point a path scattered at, or passed through
+#[derive(Debug, Clone)]
+pub struct PathVertex {
+    pub point: Vector3<f64>,
+    /// product of the bsdf weights along the path up to this point, 1 at the camera
+    pub throughput: Vector3<f64>,
+}
+
+/// everything one camera sample did, starting at the camera
+#[derive(Debug, Clone)]
+pub struct LightPath {
+    pub pixel: (usize, usize),
+    pub sample: usize,
+    pub vertices: Vec<PathVertex>,
+    /// direction of the last ray if it left the scene
+    pub escape: Option<Vector3<f64>>,
+    /// what the sample added to the pixel
+    pub radiance: Vector3<f64>,
+}
+
+/// receives the paths of the samples it asks for, see `Renderer::render_recorded`
+pub trait PathRecorder: Sync {
+    fn records(&self, pixel: (usize, usize), sample: usize) -> bool;
+    /// called once per render with every recorded path, in the same order for every
+    /// thread count
+    fn finish(&self, paths: Vec<LightPath>) -> Result<(), std::io::Error>;
+}
+
+/// which samples to record, everything by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathFilter {
+    /// columns and rows
+    pub pixels: Option<(Range<usize>, Range<usize>)>,
+    pub samples: Option<Range<usize>>,
+}
+
+impl PathFilter {
+    pub fn matches(&self, pixel: (usize, usize), sample: usize) -> bool {
+        let in_region = self
+            .pixels
+            .as_ref()
+            .is_none_or(|(x, y)| x.contains(&pixel.0) && y.contains(&pixel.1));
+        in_region && self.samples.as_ref().is_none_or(|s| s.contains(&sample))
+    }
+}
+
+/// writes every path as a polyline with the throughput as vertex color, which most obj
+/// viewers show. a comment before each names its pixel, sample and radiance
+#[derive(Debug, Clone)]
+pub struct ObjPathRecorder {
+    pub path: String,
+    pub filter: PathFilter,
+    /// scene units per obj unit, so that the paths line up with the loaded obj files
+    pub scale: f64,
+    /// how far escaping rays are drawn, in scene units
+    pub escape_length: f64,
+}
+
+impl ObjPathRecorder {
+    pub fn new(path: &str, filter: PathFilter) -> ObjPathRecorder {
+        ObjPathRecorder {
+            path: path.to_string(),
+            filter,
+            scale: PIXELS_PER_METER,
+            escape_length: 200.0,
+        }
+    }
+
+    fn write_vertex(&self, text: &mut String, point: &Vector3<f64>, color: &Vector3<f64>) {
+        let p = point * (1.0 / self.scale);
+        writeln!(
+            text,
+            "v {} {} {} {} {} {}",
+            p[0], p[1], p[2], color[0], color[1], color[2]
+        )
+        .unwrap();
+    }
+}
+
+impl PathRecorder for ObjPathRecorder {
+    fn records(&self, pixel: (usize, usize), sample: usize) -> bool {
+        self.filter.matches(pixel, sample)
+    }
+
+    fn finish(&self, paths: Vec<LightPath>) -> Result<(), std::io::Error> {
+        let mut text = String::new();
+        let mut count = 0;
+        for path in &paths {
+            let (x, y) = path.pixel;
+            let r = &path.radiance;
+            writeln!(
+                text,
+                "# pixel {x} {y} sample {} radiance {} {} {}\no path_{x}_{y}_{}",
+                path.sample, r[0], r[1], r[2], path.sample
+            )
+            .unwrap();
+            for vertex in &path.vertices {
+                self.write_vertex(&mut text, &vertex.point, &vertex.throughput);
+            }
+            let mut len = path.vertices.len();
+            if let (Some(dir), Some(last)) = (&path.escape, path.vertices.last()) {
+                let end = &last.point + dir * self.escape_length;
+                self.write_vertex(&mut text, &end, &last.throughput);
+                len += 1;
+            }
+            if len > 1 {
+                text.push('l');
+                for i in count..count + len {
+                    write!(text, " {}", i + 1).unwrap();
+                }
+                text.push('\n');
+            }
+            count += len;
+        }
+        std::fs::write(&self.path, text)
+    }
+}
@@ -0,0 +1,419 @@
+mod settings;
+
+pub use settings::{Integrator, RenderSettings, RenderSettingsBuilder, SettingsError};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::bsdf::Bsdf;
+use crate::camera::Camera;
+use crate::image::Image;
+use crate::obj::{LoadOptions, ObjError};
+use crate::rand::NormalDist;
+use crate::ray::Ray;
+use crate::recorder::{LightPath, PathRecorder, PathVertex};
+use crate::scene::{Hit, Scene};
+use crate::vector::Vector3;
+
+const TILE_SIZE: usize = 16;
+
+// https://www.pbr-book.org/4ed/Monte_Carlo_Integration/Improving_Efficiency#PowerHeuristic
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a.is_infinite() {
+        return 1.0;
+    }
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+struct Tile {
+    origin: (usize, usize),
+    size: (usize, usize),
+}
+
+struct RenderedTile {
+    // covers the tile plus the reach of the filter on every side
+    image: Image,
+    margin: usize,
+    paths: Vec<LightPath>,
+}
+
+#[derive(Debug)]
+pub struct Renderer {
+    scene: Scene,
+    camera: Camera,
+    settings: RenderSettings,
+}
+
+impl Renderer {
+    /// fails if `settings` don't pass `RenderSettings::validate`
+    pub fn new(camera: Camera, settings: RenderSettings) -> Result<Renderer, SettingsError> {
+        settings.validate()?;
+        Ok(Renderer {
+            scene: Scene::default(),
+            camera,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: RenderSettings) -> Result<(), SettingsError> {
+        settings.validate()?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    pub fn load_obj(&mut self, path: &str) -> Result<(), ObjError> {
+        self.scene = Scene::from_obj(path)?;
+        Ok(())
+    }
+
+    pub fn load_obj_with(&mut self, path: &str, options: &LoadOptions) -> Result<(), ObjError> {
+        self.scene = Scene::from_obj_with(path, options)?;
+        Ok(())
+    }
+
+    /// renders a scene put together by hand, builds it first so edits to its instances
+    /// are picked up
+    pub fn set_scene(&mut self, mut scene: Scene) {
+        scene.build();
+        self.scene = scene;
+    }
+
+    /// path tracer with next event estimation, light and bsdf samples are combined with
+    /// multiple importance sampling. after `rr_depth` segments paths are ended at random
+    /// by russian roulette, so `max_depth` only guards against paths that never end
+    /// https://www.pbr-book.org/4ed/Monte_Carlo_Integration/Improving_Efficiency#RussianRoulette
+    fn color_ray(
+        &self,
+        init_ray: &Ray,
+        rand_state: &mut NormalDist,
+        mut path: Option<&mut LightPath>,
+    ) -> Vector3<f64> {
+        let mut ray_color = Vector3::<f64>::new(1.0, 1.0, 1.0);
+        let mut light = Vector3::<f64>::default();
+        let mut ray = Ray::new(init_ray.orig().clone(), init_ray.dir().normalized());
+        // pdf of the bsdf sample that produced `ray`, None for camera rays and delta lobes
+        let mut bsdf_pdf: Option<f64> = None;
+        // distance travelled since the last scattering event, rays that pass through
+        // transparent surfaces keep going
+        let mut segment_len = 0.0;
+        // the direct integrator stops at the first surface after the first scattering
+        let mut scattered = false;
+        for depth in 1..=self.settings.max_depth {
+            let Some(hit) = self.scene.hits(&ray, self.settings.acne_threshold) else {
+                if let Some(path) = path.as_deref_mut() {
+                    path.escape = Some(ray.dir().clone());
+                }
+                break;
+            };
+            let (instance, triangle) = (hit.instance, hit.triangle);
+            let hit_point = ray.point_at(hit.t);
+            segment_len += hit.t;
+            if let Some(path) = path.as_deref_mut() {
+                path.vertices.push(PathVertex {
+                    point: hit_point.clone(),
+                    throughput: ray_color.clone(),
+                });
+            }
+            let material = self
+                .scene
+                .get_surface_material(instance, triangle, hit.u, hit.v);
+            let opacity = material.base.opacity;
+            if opacity < 1.0 && rand_state.uniform() >= opacity {
+                ray = Ray::new(hit_point, ray.dir().clone());
+                continue;
+            }
+
+            let wo = -ray.dir();
+            let face_normal = self.scene.get_geometric_normal(&hit);
+            let cos_o = face_normal.dot(&wo);
+
+            if material.is_emissive() {
+                let weight = match bsdf_pdf {
+                    None => 1.0,
+                    Some(pdf) => {
+                        let light_pdf = self.scene.light_pdf(&hit) * segment_len * segment_len
+                            / cos_o.abs().max(1e-12);
+                        power_heuristic(pdf, light_pdf)
+                    }
+                };
+                let mut emitted = &material.emission * weight;
+                emitted.mul_element_wise(&ray_color);
+                light += emitted;
+            }
+            if scattered && self.settings.integrator == Integrator::Direct {
+                break;
+            }
+
+            let geometric_normal = if cos_o < 0.0 {
+                -face_normal
+            } else {
+                face_normal
+            };
+            // interpolated normals are flipped along with the face, and can still end up
+            // facing away from the viewer near silhouettes
+            let mut shading_normal = self.scene.get_shading_normal(&hit);
+            if shading_normal.dot(&geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            if shading_normal.dot(&wo) <= 0.0 {
+                shading_normal = geometric_normal.clone();
+            }
+            let bsdf = Bsdf::new(&material, &shading_normal, &geometric_normal, cos_o >= 0.0);
+            if !bsdf.is_delta() {
+                light += self.sample_direct(&bsdf, &hit_point, &wo, &ray_color, rand_state);
+            }
+
+            let Some(sample) = bsdf.sample(&wo, rand_state) else {
+                break;
+            };
+            ray_color.mul_element_wise(&sample.weight);
+            bsdf_pdf = (!sample.delta).then_some(sample.pdf);
+            segment_len = 0.0;
+            scattered = true;
+            ray = Ray::new(hit_point, sample.dir);
+
+            // dim paths are likely to end, the ones that survive carry their share
+            if depth >= self.settings.rr_depth {
+                let survival = ray_color.max_elem().min(1.0);
+                if rand_state.uniform() >= survival {
+                    break;
+                }
+                ray_color *= 1.0 / survival;
+            }
+        }
+        if let Some(path) = path {
+            path.radiance = light.clone();
+        }
+        light
+    }
+
+    /// the shading normal at the first surface `ray` hits, black if it hits nothing
+    fn normal_color(&self, ray: &Ray, path: Option<&mut LightPath>) -> Vector3<f64> {
+        let hit = self.scene.hits(ray, self.settings.acne_threshold);
+        let color = match &hit {
+            Some(hit) => (self.scene.get_shading_normal(hit) + Vector3::new(1.0, 1.0, 1.0)) * 0.5,
+            None => Vector3::default(),
+        };
+        if let Some(path) = path {
+            match &hit {
+                Some(hit) => path.vertices.push(PathVertex {
+                    point: ray.point_at(hit.t),
+                    throughput: Vector3::new(1.0, 1.0, 1.0),
+                }),
+                None => path.escape = Some(ray.dir().clone()),
+            }
+            path.radiance = color.clone();
+        }
+        color
+    }
+
+    /// light arriving at `point` straight from a sampled point on an emitter
+    fn sample_direct(
+        &self,
+        bsdf: &Bsdf,
+        point: &Vector3<f64>,
+        wo: &Vector3<f64>,
+        ray_color: &Vector3<f64>,
+        rand_state: &mut NormalDist,
+    ) -> Vector3<f64> {
+        let Some(sample) = self.scene.sample_light(rand_state) else {
+            return Vector3::default();
+        };
+        let to_light = &sample.point - point;
+        let dist = to_light.len();
+        let wi = to_light * (1.0 / dist);
+        let cos_light = sample.normal.dot(&wi).abs();
+        let mut f = bsdf.eval(wo, &wi);
+        if cos_light <= 0.0 || f.max_elem() <= 0.0 {
+            return Vector3::default();
+        }
+
+        let transmittance =
+            self.transmittance(point, &wi, dist, (sample.instance, sample.triangle));
+        if transmittance <= 0.0 {
+            return Vector3::default();
+        }
+
+        let light_pdf = sample.pdf * dist * dist / cos_light;
+        let weight = power_heuristic(light_pdf, bsdf.pdf(wo, &wi));
+        let light_material =
+            self.scene
+                .get_surface_material(sample.instance, sample.triangle, sample.u, sample.v);
+        f.mul_element_wise(&light_material.emission);
+        f.mul_element_wise(ray_color);
+        f * (transmittance * light_material.base.opacity * weight / light_pdf)
+    }
+
+    /// fraction of light that makes it `dist` along `dir` through partially transparent
+    /// surfaces, stopping early at `target`
+    fn transmittance(
+        &self,
+        from: &Vector3<f64>,
+        dir: &Vector3<f64>,
+        dist: f64,
+        target: (usize, usize),
+    ) -> f64 {
+        let mut result = 1.0;
+        let mut ray = Ray::new(from.clone(), dir.clone());
+        let mut remaining = dist * (1.0 - 1e-6);
+        while let Some(Hit {
+            t,
+            instance,
+            triangle,
+            ..
+        }) = self.scene.hits(&ray, self.settings.acne_threshold)
+        {
+            if (instance, triangle) == target || t >= remaining {
+                break;
+            }
+            let material = self.scene.get_material(instance, triangle);
+            if material.is_dielectric() || material.opacity >= 1.0 {
+                return 0.0;
+            }
+            result *= 1.0 - material.opacity;
+            remaining -= t;
+            ray = Ray::new(ray.point_at(t), dir.clone());
+        }
+        result
+    }
+
+    pub fn render(&self) -> Image {
+        self.render_paths(None).0
+    }
+
+    /// renders while handing the paths of the samples `recorder` asks for to it
+    pub fn render_recorded(&self, recorder: &dyn PathRecorder) -> Result<Image, std::io::Error> {
+        let (image, paths) = self.render_paths(Some(recorder));
+        recorder.finish(paths)?;
+        Ok(image)
+    }
+
+    fn render_paths(&self, recorder: Option<&dyn PathRecorder>) -> (Image, Vec<LightPath>) {
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let rendered: Mutex<Vec<Option<RenderedTile>>> =
+            Mutex::new((0..tiles.len()).map(|_| None).collect());
+
+        thread::scope(|s| {
+            let threads = self
+                .settings
+                .threads
+                .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            for _ in 0..threads.min(tiles.len()) {
+                s.spawn(|| loop {
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let Some(tile) = tiles.get(index) else {
+                        break;
+                    };
+                    let result = self.render_tile(index, tile, recorder);
+                    rendered.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        // merge in tile order so that neither the image nor the paths depend on scheduling
+        let mut image = Image::new(self.camera.resolution());
+        let mut paths = Vec::new();
+        for (tile, result) in tiles.iter().zip(rendered.into_inner().unwrap()) {
+            let result = result.expect("every tile is rendered");
+            let offset = (
+                tile.origin.0 as isize - result.margin as isize,
+                tile.origin.1 as isize - result.margin as isize,
+            );
+            image.merge(&result.image, offset);
+            paths.extend(result.paths);
+        }
+        image.resolve();
+        (image, paths)
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let (width, height) = self.camera.resolution();
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(TILE_SIZE) {
+            for x in (0..width).step_by(TILE_SIZE) {
+                tiles.push(Tile {
+                    origin: (x, y),
+                    size: (TILE_SIZE.min(width - x), TILE_SIZE.min(height - y)),
+                });
+            }
+        }
+        tiles
+    }
+
+    fn render_tile(
+        &self,
+        index: usize,
+        tile: &Tile,
+        recorder: Option<&dyn PathRecorder>,
+    ) -> RenderedTile {
+        // one random stream per tile keeps the result independent of the thread count
+        let mut rand_state = NormalDist::from_stream(self.settings.seed, index as u64);
+        let mut paths = Vec::new();
+        let filter = &self.settings.filter;
+        let margin = filter.radius().ceil() as usize;
+        let mut image = Image::new((tile.size.0 + 2 * margin, tile.size.1 + 2 * margin));
+
+        // jitter inside a grid of strata, any samples that don't fill a row are uniform
+        let strata = (self.settings.spp as f64).sqrt() as usize;
+        for j in tile.origin.1..tile.origin.1 + tile.size.1 {
+            for i in tile.origin.0..tile.origin.0 + tile.size.0 {
+                for sample in 0..self.settings.spp as usize {
+                    let (sx, sy) = if sample < strata * strata {
+                        (
+                            ((sample % strata) as f64 + rand_state.uniform()) / strata as f64,
+                            ((sample / strata) as f64 + rand_state.uniform()) / strata as f64,
+                        )
+                    } else {
+                        (rand_state.uniform(), rand_state.uniform())
+                    };
+                    let film = (i as f64 + sx, j as f64 + sy);
+                    let r = self.camera.generate_ray(film, &mut rand_state);
+                    let mut path = recorder
+                        .is_some_and(|recorder| recorder.records((i, j), sample))
+                        .then(|| LightPath {
+                            pixel: (i, j),
+                            sample,
+                            vertices: vec![PathVertex {
+                                point: r.orig().clone(),
+                                throughput: Vector3::new(1.0, 1.0, 1.0),
+                            }],
+                            escape: None,
+                            radiance: Vector3::default(),
+                        });
+                    let mut color = match self.settings.integrator {
+                        Integrator::Normals => self.normal_color(&r, path.as_mut()),
+                        Integrator::Path | Integrator::Direct => {
+                            self.color_ray(&r, &mut rand_state, path.as_mut())
+                        }
+                    };
+                    if let Some(clamp) = self.settings.clamp {
+                        for c in 0..3 {
+                            color[c] = color[c].min(clamp);
+                        }
+                    }
+                    paths.extend(path);
+                    let local = (
+                        film.0 - tile.origin.0 as f64 + margin as f64,
+                        film.1 - tile.origin.1 as f64 + margin as f64,
+                    );
+                    image.add_sample(local, &color, filter);
+                }
+            }
+        }
+        RenderedTile {
+            image,
+            margin,
+            paths,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Filter;
+
+// some random nums i generated online
+const DEFAULT_SEED: u64 = 0x04b22c5e9310d9cb;
+
+/// what `Renderer` computes for every camera ray
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrator {
+    /// every bounce, with next event estimation and multiple importance sampling
+    #[default]
+    Path,
+    /// only light that reaches the first surface straight from an emitter
+    Direct,
+    /// shading normals mapped from -1..1 to 0..1, for looking at the geometry
+    Normals,
+}
+
+impl FromStr for Integrator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Integrator, String> {
+        match s {
+            "path" => Ok(Integrator::Path),
+            "direct" => Ok(Integrator::Direct),
+            "normals" => Ok(Integrator::Normals),
+            _ => Err(format!(
+                "unknown integrator `{s}`, expected path, direct or normals"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Integrator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Integrator::Path => "path",
+            Integrator::Direct => "direct",
+            Integrator::Normals => "normals",
+        })
+    }
+}
+
+/// everything about how an image is rendered apart from the camera and the scene. it
+/// round-trips through toml so it can be kept next to the images it made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// samples per pixel
+    #[serde(alias = "rays_per_pixel")]
+    pub spp: u32,
+    /// the most segments a path can have, counting the camera ray. russian roulette ends
+    /// nearly every path long before, this only stops ones bouncing between mirrors
+    #[serde(alias = "max_bounces")]
+    pub max_depth: u32,
+    /// segments a path has before russian roulette may end it, the longer the less noise
+    /// from paths that end early
+    pub rr_depth: u32,
+    /// images rendered with the same seed are identical regardless of the thread count
+    pub seed: u64,
+    /// every available core if not given
+    pub threads: Option<usize>,
+    /// the most any color channel of a single sample can add, which trades a little
+    /// energy for fewer fireflies
+    pub clamp: Option<f64>,
+    pub integrator: Integrator,
+    /// hits closer than this to a ray's origin are ignored, so that surfaces don't
+    /// shadow themselves
+    pub acne_threshold: f64,
+    pub filter: Filter,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            spp: 16,
+            max_depth: 64,
+            rr_depth: 3,
+            seed: DEFAULT_SEED,
+            threads: None,
+            clamp: None,
+            integrator: Integrator::default(),
+            acne_threshold: 0.001,
+            filter: Filter::default(),
+        }
+    }
+}
+
+/// a setting outside of the values it can take
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsError {
+    pub setting: &'static str,
+    pub value: String,
+    pub expected: &'static str,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid render setting `{}` = {}, expected {}",
+            self.setting, self.value, self.expected
+        )
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl RenderSettings {
+    pub fn builder() -> RenderSettingsBuilder {
+        RenderSettingsBuilder::default()
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let check = |ok: bool, setting, value: &dyn fmt::Display, expected| {
+            if ok {
+                Ok(())
+            } else {
+                Err(SettingsError {
+                    setting,
+                    value: value.to_string(),
+                    expected,
+                })
+            }
+        };
+        check(self.spp > 0, "spp", &self.spp, "at least 1")?;
+        check(
+            self.max_depth > 0,
+            "max_depth",
+            &self.max_depth,
+            "at least 1",
+        )?;
+        if let Some(threads) = self.threads {
+            check(threads > 0, "threads", &threads, "at least 1")?;
+        }
+        if let Some(clamp) = self.clamp {
+            check(clamp > 0.0, "clamp", &clamp, "a positive number")?;
+        }
+        check(
+            self.acne_threshold >= 0.0 && self.acne_threshold.is_finite(),
+            "acne_threshold",
+            &self.acne_threshold,
+            "a finite number of at least 0",
+        )?;
+        let radius = self.filter.radius();
+        check(
+            radius > 0.0 && radius.is_finite(),
+            "filter.radius",
+            &radius,
+            "a positive number",
+        )
+    }
+
+    /// parses and validates
+    pub fn from_toml(text: &str) -> Result<RenderSettings, String> {
+        let settings: RenderSettings = toml::from_str(text).map_err(|e| e.to_string())?;
+        settings.validate().map_err(|e| e.to_string())?;
+        Ok(settings)
+    }
+
+    /// fails for seeds above `i64::MAX`, which toml has no integers for
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+}
+
+/// `RenderSettings` starting from the defaults, checked once at the end
+#[derive(Debug, Clone, Default)]
+pub struct RenderSettingsBuilder {
+    settings: RenderSettings,
+}
+
+impl RenderSettingsBuilder {
+    pub fn spp(mut self, spp: u32) -> Self {
+        self.settings.spp = spp;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.settings.max_depth = max_depth;
+        self
+    }
+
+    pub fn rr_depth(mut self, rr_depth: u32) -> Self {
+        self.settings.rr_depth = rr_depth;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.settings.seed = seed;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.settings.threads = Some(threads);
+        self
+    }
+
+    pub fn clamp(mut self, clamp: f64) -> Self {
+        self.settings.clamp = Some(clamp);
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.settings.integrator = integrator;
+        self
+    }
+
+    pub fn acne_threshold(mut self, acne_threshold: f64) -> Self {
+        self.settings.acne_threshold = acne_threshold;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.settings.filter = filter;
+        self
+    }
+
+    pub fn build(self) -> Result<RenderSettings, SettingsError> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+}
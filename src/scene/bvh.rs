@@ -0,0 +1,415 @@
+use crate::{
+    ray::Ray,
+    vector::{Transform, Vector3},
+};
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// relative cost of one ray-box test compared to one ray-primitive test
+const TRAVERSAL_COST: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct Aabb {
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+}
+
+impl Default for Aabb {
+    fn default() -> Aabb {
+        Aabb {
+            min: Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+}
+
+impl Aabb {
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vector3<f64>>) -> Aabb {
+        let mut result = Aabb::default();
+        for p in points {
+            result.grow_point(p);
+        }
+        result
+    }
+
+    pub fn grow_point(&mut self, p: &Vector3<f64>) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(p[i]);
+            self.max[i] = self.max[i].max(p[i]);
+        }
+    }
+
+    pub fn grow(&mut self, other: &Aabb) {
+        self.grow_point(&other.min);
+        self.grow_point(&other.max);
+    }
+
+    pub fn centroid(&self) -> Vector3<f64> {
+        (&self.min + &self.max) * 0.5
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    pub fn is_finite(&self) -> bool {
+        !self.is_empty() && (0..3).all(|i| self.min[i].is_finite() && self.max[i].is_finite())
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = &self.max - &self.min;
+        2.0 * (d[0] * d[1] + d[1] * d[2] + d[2] * d[0])
+    }
+
+    /// box around the eight transformed corners
+    pub fn transformed(&self, transform: &Transform) -> Aabb {
+        if self.is_empty() {
+            return Aabb::default();
+        }
+        let corners: Vec<Vector3<f64>> = (0..8)
+            .map(|i| {
+                let corner = Vector3::new(
+                    if i & 1 == 0 { self.min[0] } else { self.max[0] },
+                    if i & 2 == 0 { self.min[1] } else { self.max[1] },
+                    if i & 4 == 0 { self.min[2] } else { self.max[2] },
+                );
+                transform.point(&corner)
+            })
+            .collect();
+        Aabb::from_points(&corners)
+    }
+
+    /// slab test, returns the distance at which the ray enters the box
+    pub fn hit(&self, ray: &Ray, inv_dir: &Vector3<f64>, t_max: f64) -> Option<f64> {
+        let mut t0 = 0.0_f64;
+        let mut t1 = t_max;
+        for i in 0..3 {
+            let mut near = (self.min[i] - ray.orig()[i]) * inv_dir[i];
+            let mut far = (self.max[i] - ray.orig()[i]) * inv_dir[i];
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // written so that NaNs (0 * inf) leave the interval untouched
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some(t0)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+    bounds: Aabb,
+    // leaf: offset into `indices`, interior: index of the second child (the first is
+    // always stored directly after its parent)
+    offset: usize,
+    // zero for interior nodes
+    count: usize,
+    axis: usize,
+}
+
+fn bin_index(centroid: f64, min: f64, scale: f64) -> usize {
+    (((centroid - min) * scale) as usize).min(BIN_COUNT - 1)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bin {
+    count: usize,
+    bounds_min: [f64; 3],
+    bounds_max: [f64; 3],
+}
+
+impl Default for Bin {
+    fn default() -> Bin {
+        Bin {
+            count: 0,
+            bounds_min: [f64::INFINITY; 3],
+            bounds_max: [f64::NEG_INFINITY; 3],
+        }
+    }
+}
+
+impl Bin {
+    fn grow(&mut self, bounds: &Aabb) {
+        for i in 0..3 {
+            self.bounds_min[i] = self.bounds_min[i].min(bounds.min[i]);
+            self.bounds_max[i] = self.bounds_max[i].max(bounds.max[i]);
+        }
+    }
+
+    fn merge(&mut self, other: &Bin) {
+        self.count += other.count;
+        for i in 0..3 {
+            self.bounds_min[i] = self.bounds_min[i].min(other.bounds_min[i]);
+            self.bounds_max[i] = self.bounds_max[i].max(other.bounds_max[i]);
+        }
+    }
+
+    fn surface_area(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let d: [f64; 3] = std::array::from_fn(|i| self.bounds_max[i] - self.bounds_min[i]);
+        2.0 * (d[0] * d[1] + d[1] * d[2] + d[2] * d[0])
+    }
+}
+
+/// bounding volume hierarchy over an arbitrary list of primitives, built with binned SAH
+#[derive(Debug, Default, Clone)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+        };
+        if bounds.is_empty() {
+            return bvh;
+        }
+        let centroids: Vec<Vector3<f64>> = bounds.iter().map(Aabb::centroid).collect();
+        bvh.build_node(bounds, &centroids, 0, bounds.len());
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|n| n.bounds.clone())
+            .unwrap_or_default()
+    }
+
+    fn build_node(
+        &mut self,
+        bounds: &[Aabb],
+        centroids: &[Vector3<f64>],
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let mut node_bounds = Aabb::default();
+        let mut centroid_bounds = Aabb::default();
+        for &i in &self.indices[start..end] {
+            node_bounds.grow(&bounds[i]);
+            centroid_bounds.grow_point(&centroids[i]);
+        }
+        self.nodes.push(Node {
+            bounds: node_bounds.clone(),
+            offset: start,
+            count: end - start,
+            axis: 0,
+        });
+
+        let count = end - start;
+        if count == 1 {
+            return node_index;
+        }
+
+        let Some((axis, mid)) = self.find_split(
+            bounds,
+            centroids,
+            &node_bounds,
+            &centroid_bounds,
+            start,
+            end,
+        ) else {
+            return node_index;
+        };
+
+        self.build_node(bounds, centroids, start, mid);
+        let second = self.build_node(bounds, centroids, mid, end);
+        let node = &mut self.nodes[node_index];
+        node.offset = second;
+        node.count = 0;
+        node.axis = axis;
+        node_index
+    }
+
+    /// partitions `indices[start..end]` and returns the split axis and position, or `None`
+    /// if the primitives are cheaper to intersect as a single leaf
+    fn find_split(
+        &mut self,
+        bounds: &[Aabb],
+        centroids: &[Vector3<f64>],
+        node_bounds: &Aabb,
+        centroid_bounds: &Aabb,
+        start: usize,
+        end: usize,
+    ) -> Option<(usize, usize)> {
+        let count = end - start;
+        let extent = &centroid_bounds.max - &centroid_bounds.min;
+        let axis = (0..3)
+            .max_by(|a, b| extent[*a].total_cmp(&extent[*b]))
+            .unwrap();
+
+        if extent[axis] <= 0.0 {
+            // every centroid coincides, no plane can separate them
+            if count <= MAX_LEAF_SIZE {
+                return None;
+            }
+            return Some((axis, start + count / 2));
+        }
+
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+            let scale = BIN_COUNT as f64 / extent[axis];
+            let bin_of = |i: usize| bin_index(centroids[i][axis], centroid_bounds.min[axis], scale);
+
+            let mut bins = [Bin::default(); BIN_COUNT];
+            for &i in &self.indices[start..end] {
+                let bin = &mut bins[bin_of(i)];
+                bin.count += 1;
+                bin.grow(&bounds[i]);
+            }
+
+            // sweep from the right, then from the left, to get the cost of every plane
+            let mut right_area = [0.0; BIN_COUNT];
+            let mut right_count = [0; BIN_COUNT];
+            let mut acc = Bin::default();
+            for b in (1..BIN_COUNT).rev() {
+                acc.merge(&bins[b]);
+                right_area[b] = acc.surface_area();
+                right_count[b] = acc.count;
+            }
+            let mut acc = Bin::default();
+            for b in 0..BIN_COUNT - 1 {
+                acc.merge(&bins[b]);
+                let cost = acc.count as f64 * acc.surface_area()
+                    + right_count[b + 1] as f64 * right_area[b + 1];
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        let (cost, axis, bin) = best?;
+        let split_cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(f64::MIN_POSITIVE);
+        if count <= MAX_LEAF_SIZE && split_cost >= count as f64 {
+            return None;
+        }
+
+        let scale = BIN_COUNT as f64 / extent[axis];
+        let min = centroid_bounds.min[axis];
+        let in_right = |i: usize| bin_index(centroids[i][axis], min, scale) > bin;
+        self.indices[start..end].sort_by_key(|&i| in_right(i));
+        let mid = start
+            + self.indices[start..end]
+                .iter()
+                .take_while(|&&i| !in_right(i))
+                .count();
+        if mid == start || mid == end {
+            return Some((axis, start + count / 2));
+        }
+        Some((axis, mid))
+    }
+
+    /// walks the tree front to back and returns the closest hit reported by `intersect`,
+    /// which is given the index of a primitive and the current closest distance
+    pub fn closest_hit<T>(
+        &self,
+        ray: &Ray,
+        intersect: impl FnMut(usize, f64) -> Option<(f64, T)>,
+    ) -> Option<(f64, T)> {
+        self.closest_hit_within(ray, f64::INFINITY, intersect)
+    }
+
+    /// `closest_hit` that ignores anything at or beyond `t_max`, for nesting one tree in
+    /// the leaves of another
+    pub fn closest_hit_within<T>(
+        &self,
+        ray: &Ray,
+        mut t_max: f64,
+        mut intersect: impl FnMut(usize, f64) -> Option<(f64, T)>,
+    ) -> Option<(f64, T)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_dir = ray.dir().apply(|x| 1.0 / x);
+        let dir_is_neg = [inv_dir[0] < 0.0, inv_dir[1] < 0.0, inv_dir[2] < 0.0];
+
+        let mut result: Option<(f64, T)> = None;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds.hit(ray, &inv_dir, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &prim in &self.indices[node.offset..node.offset + node.count] {
+                    if let Some((t, hit)) = intersect(prim, t_max) {
+                        if t < t_max {
+                            t_max = t;
+                            result = Some((t, hit));
+                        }
+                    }
+                }
+            } else if dir_is_neg[node.axis] {
+                stack.push(node_index + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(node_index + 1);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::NormalDist;
+
+    #[test]
+    fn closest_hit_matches_brute_force() {
+        let mut rand = NormalDist::new([1, 2]);
+        let mut point = |size: f64| {
+            Vector3::new(
+                size * rand.normal(),
+                size * rand.normal(),
+                size * rand.normal(),
+            )
+        };
+        let boxes: Vec<Aabb> = (0..500)
+            .map(|_| {
+                let center = point(5.0);
+                Aabb::from_points([&center + &point(0.5), &center + point(0.5)].iter())
+            })
+            .collect();
+        let bvh = Bvh::build(&boxes);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            // from well outside the cloud of boxes towards somewhere inside it
+            let mut orig = point(1.0);
+            orig.normalize();
+            let orig = orig * 100.0;
+            let dir = point(5.0) - &orig;
+            let ray = Ray::new(orig, dir);
+            let inv_dir = ray.dir().apply(|x| 1.0 / x);
+            let expected = (0..boxes.len())
+                .filter_map(|i| Some((boxes[i].hit(&ray, &inv_dir, f64::INFINITY)?, i)))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            let found = bvh.closest_hit(&ray, |i, t_max| {
+                Some((boxes[i].hit(&ray, &inv_dir, t_max)?, i))
+            });
+            assert_eq!(found, expected);
+            hits += found.is_some() as usize;
+        }
+        // most rays aim into the cloud of boxes
+        assert!(hits > 500, "{hits}");
+    }
+}
@@ -0,0 +1,65 @@
+use std::ops::Range;
+
+use super::{
+    bvh::{Aabb, Bvh},
+    shape::Shape,
+};
+use crate::{ray::Ray, vector::Transform};
+
+#[derive(Debug)]
+pub enum Geometry {
+    /// a contiguous range of the scene's triangles
+    Mesh(Range<usize>),
+    /// an analytic surface, with the material it is drawn with
+    Shape {
+        shape: Box<dyn Shape>,
+        material: usize,
+    },
+}
+
+/// something that can be placed any number of times, in object space
+#[derive(Debug)]
+pub struct Prototype {
+    pub geometry: Geometry,
+    /// over the triangles of a mesh, primitive indices are relative to its start
+    pub(super) bvh: Bvh,
+}
+
+impl Prototype {
+    pub fn bounds(&self) -> Aabb {
+        match &self.geometry {
+            Geometry::Mesh(_) => self.bvh.bounds(),
+            Geometry::Shape { shape, .. } => shape.bounds(),
+        }
+    }
+}
+
+/// a placement of a prototype in the world
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub prototype: usize,
+    /// object to world
+    pub transform: Transform,
+    /// used for every triangle instead of its own material
+    pub material: Option<usize>,
+}
+
+impl Instance {
+    pub fn new(prototype: usize, transform: Transform) -> Instance {
+        Instance {
+            prototype,
+            transform,
+            material: None,
+        }
+    }
+
+    /// `ray` in object space. the direction keeps the scale of the transform so that
+    /// distances along it are the same in both spaces
+    pub fn to_object(&self, ray: &Ray) -> Ray {
+        let inverse = self.transform.inverse_matrix();
+        Ray::new(
+            inverse.transform_point(ray.orig()),
+            inverse.transform_direction(ray.dir()),
+        )
+    }
+}
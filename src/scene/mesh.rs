@@ -4,18 +4,147 @@ use crate::vector::Vector3;
 pub struct Triangle {
     pub vertices: [usize; 3],
     pub point_normals: [usize; 3],
+    /// indices into `Scene::texture_coords`, if the face had any
+    pub texture_coords: Option<[usize; 3]>,
+    /// indices into `Scene::tangents`, whenever there are texture coordinates
+    pub tangents: Option<[usize; 3]>,
     pub material: usize,
     pub face_normal: usize,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct Material {
     pub diffuse: Vector3<f64>,
     pub emission: Vector3<f64>,
-    // pub ambient: Vector3<f64>,
-    // pub specular: Vector3<f64>,
-    // pub filter: Vector3<f64>,
-    // pub specular_exp: f64,
-    // pub opacity: f64,
-    // pub density: f64,
+    pub ambient: Vector3<f64>,
+    pub specular: Vector3<f64>,
+    /// tint of light refracted through a dielectric
+    pub filter: Vector3<f64>,
+    /// phong exponent, converted to a microfacet roughness by `roughness`
+    pub specular_exp: f64,
+    pub opacity: f64,
+    /// index of refraction, called optical density in mtl files
+    pub density: f64,
+    pub illum: u8,
+    // indices into `Scene::textures`, each map is multiplied with the value above
+    pub diffuse_map: Option<usize>,
+    pub emission_map: Option<usize>,
+    pub specular_map: Option<usize>,
+    pub specular_exp_map: Option<usize>,
+    /// tangent space normals, used instead of the interpolated vertex normals
+    pub normal_map: Option<usize>,
+    /// heights, in scene units once multiplied with `bump_height`
+    pub bump_map: Option<usize>,
+    /// scales the tangential part of the normal map, unitless
+    pub normal_strength: f64,
+    /// height of a white texel in the bump map, in scene units
+    pub bump_height: f64,
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            diffuse: Vector3::default(),
+            emission: Vector3::default(),
+            ambient: Vector3::default(),
+            specular: Vector3::default(),
+            filter: Vector3::new(1.0, 1.0, 1.0),
+            specular_exp: 0.0,
+            opacity: 1.0,
+            density: 1.0,
+            illum: 2,
+            diffuse_map: None,
+            emission_map: None,
+            specular_map: None,
+            specular_exp_map: None,
+            normal_map: None,
+            bump_map: None,
+            normal_strength: 1.0,
+            bump_height: 1.0,
+        }
+    }
+}
+
+impl Material {
+    pub fn has_maps(&self) -> bool {
+        self.diffuse_map.is_some()
+            || self.emission_map.is_some()
+            || self.specular_map.is_some()
+            || self.specular_exp_map.is_some()
+    }
+
+    /// shifts every map index, for when the textures are appended to another list
+    pub fn offset_textures(&mut self, offset: usize) {
+        for map in [
+            &mut self.diffuse_map,
+            &mut self.emission_map,
+            &mut self.specular_map,
+            &mut self.specular_exp_map,
+            &mut self.normal_map,
+            &mut self.bump_map,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *map += offset;
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.max_elem() > 0.0
+    }
+
+    /// illumination models 4, 6 and 7 refract
+    pub fn is_dielectric(&self) -> bool {
+        matches!(self.illum, 4 | 6 | 7)
+    }
+
+    /// illumination model 5 is a perfect mirror with fresnel
+    pub fn is_mirror(&self) -> bool {
+        self.illum == 5
+    }
+
+    /// ggx alpha matching the phong exponent
+    pub fn roughness(&self) -> f64 {
+        roughness(self.specular_exp)
+    }
+}
+
+// https://simonstechblog.blogspot.com/2011/12/microfacet-brdf.html
+fn roughness(specular_exp: f64) -> f64 {
+    (2.0 / (specular_exp.max(0.0) + 2.0))
+        .sqrt()
+        .clamp(1e-3, 1.0)
+}
+
+/// a material at one point of a surface, holds the values its texture maps change and
+/// reads everything else from `base`
+#[derive(Debug, Clone)]
+pub struct SurfaceMaterial<'a> {
+    pub base: &'a Material,
+    pub diffuse: Vector3<f64>,
+    pub emission: Vector3<f64>,
+    pub specular: Vector3<f64>,
+    pub specular_exp: f64,
+}
+
+impl<'a> SurfaceMaterial<'a> {
+    /// `base` as it is without its maps
+    pub fn new(base: &'a Material) -> SurfaceMaterial<'a> {
+        SurfaceMaterial {
+            base,
+            diffuse: base.diffuse.clone(),
+            emission: base.emission.clone(),
+            specular: base.specular.clone(),
+            specular_exp: base.specular_exp,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.max_elem() > 0.0
+    }
+
+    pub fn roughness(&self) -> f64 {
+        roughness(self.specular_exp)
+    }
 }
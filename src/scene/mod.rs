@@ -1,118 +1,358 @@
+pub mod bvh;
+pub mod instance;
 pub mod mesh;
+pub mod shape;
 
-use crate::obj::ObjParser;
-use crate::{ray::Ray, vector::Vector3};
-use mesh::*;
-use std::f64::EPSILON;
 use std::ops::Range;
 
+use crate::obj::{LoadOptions, ObjError, ObjParser};
+use crate::{
+    rand::NormalDist,
+    ray::Ray,
+    texture::Texture,
+    vector::{Transform, Vector3},
+};
+use bvh::{Aabb, Bvh};
+use instance::{Geometry, Instance, Prototype};
+use mesh::*;
+use shape::{MeshTriangle, Shape};
+
+/// point on an emissive surface, `pdf` is with respect to area
+#[derive(Debug)]
+pub struct LightSample {
+    pub point: Vector3<f64>,
+    pub normal: Vector3<f64>,
+    pub instance: usize,
+    pub triangle: usize,
+    /// surface coordinates, as in `Hit`
+    pub u: f64,
+    pub v: f64,
+    pub pdf: f64,
+}
+
+/// closest intersection along a ray. on triangles `u` and `v` are the barycentric
+/// weights of the second and third vertex, on analytic shapes their surface coordinates
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub t: f64,
+    pub instance: usize,
+    /// zero on analytic shapes
+    pub triangle: usize,
+    pub u: f64,
+    pub v: f64,
+}
+
+/// triangles and everything they reference, shared by all prototypes. positions and
+/// normals are in object space, `instances` place them in the world
 #[derive(Debug, Default)]
 pub struct Scene {
     pub vertices: Vec<Vector3<f64>>,
     pub point_normals: Vec<Vector3<f64>>,
     pub face_normals: Vec<Vector3<f64>>,
+    pub texture_coords: Vec<(f64, f64)>,
+    pub tangents: Vec<Vector3<f64>>,
     pub triangles: Vec<Triangle>,
     pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    /// first triangle of every `o` group
     pub meshes: Vec<usize>,
+    pub prototypes: Vec<Prototype>,
+    /// changes only take effect after `build`
+    pub instances: Vec<Instance>,
+    /// over the world space bounds of the bounded instances
+    pub bvh: Bvh,
+    /// emissive primitives of every instance, as (instance, triangle)
+    pub lights: Vec<(usize, usize)>,
+    /// running total of the world space areas of `lights`
+    light_cdf: Vec<f64>,
+    /// instance of every leaf of `bvh`
+    bounded: Vec<usize>,
+    /// instances of infinite shapes, tested against every ray
+    unbounded: Vec<usize>,
 }
 
 impl Scene {
-    pub fn from_obj(path: &str) -> Result<Scene, std::io::Error> {
-        let mut parser = ObjParser::default();
+    /// loads with the default options, meters in the file become centimeters
+    pub fn from_obj(path: &str) -> Result<Scene, ObjError> {
+        Scene::from_obj_with(path, &LoadOptions::default())
+    }
+
+    /// every `o` group becomes a prototype with one instance where the file put it
+    pub fn from_obj_with(path: &str, options: &LoadOptions) -> Result<Scene, ObjError> {
+        let mut scene = Scene::default();
+        for prototype in scene.add_obj(path, options)? {
+            scene
+                .instances
+                .push(Instance::new(prototype, Transform::identity()));
+        }
+        scene.build();
+        Ok(scene)
+    }
+
+    /// loads the groups of another obj file as new prototypes without placing them, and
+    /// returns their indices
+    pub fn add_obj(&mut self, path: &str, options: &LoadOptions) -> Result<Range<usize>, ObjError> {
+        let mut parser = ObjParser::with_options(*options);
         parser.parse(path)?;
         let ObjParser {
             point_normals,
             face_normals,
             vertices,
-            triangles,
-            materials,
+            texture_coords,
+            tangents,
+            mut triangles,
+            mut materials,
+            textures,
             meshes,
             ..
         } = parser;
-        Ok(Self {
-            point_normals,
-            face_normals,
-            vertices,
-            triangles,
-            materials,
-            meshes,
-        })
-    }
 
-    pub fn hits(&self, ray: &Ray, acne_threshold: f64) -> Option<(f64, usize)> {
-        let mut result = None;
-        for i in 0..self.meshes.len() {
-            if let Some((t, triangle)) = self.hits_mesh(i, &ray, acne_threshold) {
-                result = match result {
-                    None => Some((t, triangle)),
-                    Some((prev_t, _)) => {
-                        if prev_t < t {
-                            result
-                        } else {
-                            Some((t, triangle))
-                        }
-                    }
-                };
-            }
+        let first_triangle = self.triangles.len();
+        for triangle in triangles.iter_mut() {
+            triangle.vertices = triangle.vertices.map(|i| i + self.vertices.len());
+            triangle.point_normals = triangle.point_normals.map(|i| i + self.point_normals.len());
+            triangle.texture_coords = triangle
+                .texture_coords
+                .map(|t| t.map(|i| i + self.texture_coords.len()));
+            triangle.tangents = triangle
+                .tangents
+                .map(|t| t.map(|i| i + self.tangents.len()));
+            triangle.material += self.materials.len();
+            triangle.face_normal += self.face_normals.len();
         }
-        result
+        for material in materials.iter_mut() {
+            material.offset_textures(self.textures.len());
+        }
+        self.vertices.extend(vertices);
+        self.point_normals.extend(point_normals);
+        self.face_normals.extend(face_normals);
+        self.texture_coords.extend(texture_coords);
+        self.tangents.extend(tangents);
+        self.triangles.extend(triangles);
+        self.materials.extend(materials);
+        self.textures.extend(textures);
+
+        // faces before the first `o` form a group of their own
+        let mut starts: Vec<usize> = meshes.iter().map(|m| m + first_triangle).collect();
+        if starts.first() != Some(&first_triangle) {
+            starts.insert(0, first_triangle);
+        }
+        starts.push(self.triangles.len());
+        starts.dedup();
+        self.meshes.extend(&starts[..starts.len() - 1]);
+
+        let first_prototype = self.prototypes.len();
+        for range in starts.windows(2) {
+            self.add_prototype(range[0]..range[1]);
+        }
+        Ok(first_prototype..self.prototypes.len())
     }
 
-    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
-    fn hits_mesh(&self, mesh: usize, ray: &Ray, acne_threshold: f64) -> Option<(f64, usize)> {
-        let mut result = None;
-        for triangle in self.get_triangle_range(mesh) {
-            let v0 = self.get_triangle_vertex(triangle, 0);
-            let v1 = self.get_triangle_vertex(triangle, 1);
-            let v2 = self.get_triangle_vertex(triangle, 2);
+    /// turns triangles that are already in the scene into a prototype
+    pub fn add_prototype(&mut self, triangles: Range<usize>) -> usize {
+        let bounds: Vec<Aabb> = triangles
+            .clone()
+            .map(|triangle| self.mesh_triangle(triangle).bounds())
+            .collect();
+        self.prototypes.push(Prototype {
+            geometry: Geometry::Mesh(triangles),
+            bvh: Bvh::build(&bounds),
+        });
+        self.prototypes.len() - 1
+    }
 
-            let e0 = v1 - v0;
-            let e1 = v2 - v0;
+    /// makes an analytic shape drawn with `material` into a prototype
+    pub fn add_shape(&mut self, shape: impl Shape + 'static, material: usize) -> usize {
+        self.prototypes.push(Prototype {
+            geometry: Geometry::Shape {
+                shape: Box::new(shape),
+                material,
+            },
+            bvh: Bvh::default(),
+        });
+        self.prototypes.len() - 1
+    }
 
-            let ray_x_e1 = ray.dir().cross(&e1);
-            let determinant = ray_x_e1.dot(&e0);
-            if determinant.abs() < EPSILON {
-                continue;
-            }
+    /// places a prototype, the scene has to be built again before rendering
+    pub fn add_instance(&mut self, instance: Instance) -> usize {
+        self.instances.push(instance);
+        self.instances.len() - 1
+    }
 
-            let inverse_det = 1.0 / determinant;
-            let a = ray.orig() - v0;
-            let u = inverse_det * a.dot(&ray_x_e1);
-            if u < 0.0 || u > 1.0 {
+    /// rebuilds the top level tree and the light list after `instances` changed
+    pub fn build(&mut self) {
+        self.bounded.clear();
+        self.unbounded.clear();
+        let mut bounds = Vec::new();
+        for (index, instance) in self.instances.iter().enumerate() {
+            let prototype_bounds = self.prototypes[instance.prototype].bounds();
+            if prototype_bounds.is_empty() {
                 continue;
             }
-
-            let a_x_e0 = a.cross(&e0);
-            let v = inverse_det * ray.dir().dot(&a_x_e0);
-            if v < 0.0 || u + v > 1.0 {
+            if !prototype_bounds.is_finite() {
+                self.unbounded.push(index);
                 continue;
             }
+            bounds.push(prototype_bounds.transformed(&instance.transform));
+            self.bounded.push(index);
+        }
+        self.bvh = Bvh::build(&bounds);
+        self.build_light_list();
+    }
 
-            let t = inverse_det * e1.dot(&a_x_e0);
-            if t > acne_threshold {
-                result = match result {
-                    None => Some((t, triangle)),
-                    Some((prev_t, _)) => {
-                        if prev_t < t {
-                            result
-                        } else {
-                            Some((t, triangle))
-                        }
+    fn build_light_list(&mut self) {
+        self.lights.clear();
+        self.light_cdf.clear();
+        // prototypes with no override share one list of emissive primitives
+        let emissive: Vec<Vec<usize>> = self
+            .prototypes
+            .iter()
+            .map(|p| match &p.geometry {
+                Geometry::Mesh(triangles) => triangles
+                    .clone()
+                    .filter(|&t| self.get_triangel_mat(t).is_emissive())
+                    .collect(),
+                Geometry::Shape { material, .. } => {
+                    if self.materials[*material].is_emissive() {
+                        vec![0]
+                    } else {
+                        Vec::new()
                     }
                 }
+            })
+            .collect();
+        let mut total = 0.0;
+        for (index, instance) in self.instances.iter().enumerate() {
+            let primitives: Vec<usize> = match (
+                instance.material,
+                &self.prototypes[instance.prototype].geometry,
+            ) {
+                (Some(m), _) if !self.materials[m].is_emissive() => Vec::new(),
+                (Some(_), Geometry::Mesh(triangles)) => triangles.clone().collect(),
+                (Some(_), Geometry::Shape { .. }) => vec![0],
+                (None, _) => emissive[instance.prototype].clone(),
+            };
+            for triangle in primitives {
+                let area = self.get_world_area(index, triangle);
+                if area > 0.0 && area.is_finite() {
+                    total += area;
+                    self.lights.push((index, triangle));
+                    self.light_cdf.push(total);
+                }
             }
         }
-        result
     }
 
-    fn get_triangle_range(&self, mesh: usize) -> Range<usize> {
-        let lower = self.meshes[mesh];
-        let upper = if mesh == self.meshes.len() - 1 {
-            self.triangles.len()
-        } else {
-            self.meshes[mesh + 1]
+    /// picks a light proportionally to its area, then a uniform point on it
+    pub fn sample_light(&self, rand_state: &mut NormalDist) -> Option<LightSample> {
+        let total = *self.light_cdf.last()?;
+        let target = rand_state.uniform() * total;
+        let index = self
+            .light_cdf
+            .partition_point(|&c| c <= target)
+            .min(self.lights.len() - 1);
+        let (instance, triangle) = self.lights[index];
+
+        let u = (rand_state.uniform(), rand_state.uniform());
+        let (uv, point) = self.with_shape(instance, triangle, |shape| {
+            let uv = shape.sample(u);
+            (uv, shape.point(uv))
+        });
+        let hit = Hit {
+            t: 0.0,
+            instance,
+            triangle,
+            u: uv.0,
+            v: uv.1,
         };
-        lower..upper
+        Some(LightSample {
+            point: self.instances[instance].transform.point(&point),
+            normal: self.get_geometric_normal(&hit),
+            instance,
+            triangle,
+            u: uv.0,
+            v: uv.1,
+            pdf: self.light_pdf(&hit),
+        })
+    }
+
+    /// area density with which `sample_light` picks the point that was hit
+    pub fn light_pdf(&self, hit: &Hit) -> f64 {
+        let Some(total) = self.light_cdf.last() else {
+            return 0.0;
+        };
+        if !self.get_material(hit.instance, hit.triangle).is_emissive() {
+            return 0.0;
+        }
+        // the same test `build_light_list` uses, infinite shapes like planes glow but are
+        // never sampled
+        let world_area = self.get_world_area(hit.instance, hit.triangle);
+        if !(world_area > 0.0 && world_area.is_finite()) {
+            return 0.0;
+        }
+        // uniform in object space, then stretched by the transform. for triangles this
+        // cancels out to one over the total area
+        let transform = &self.instances[hit.instance].transform;
+        let (area, normal) = self.with_shape(hit.instance, hit.triangle, |shape| {
+            (shape.area(), shape.normal((hit.u, hit.v)))
+        });
+        let stretch = transform.determinant().abs() * transform.normal(&normal).len();
+        world_area / total / (area * stretch)
+    }
+
+    pub fn hits(&self, ray: &Ray, acne_threshold: f64) -> Option<Hit> {
+        let intersect = |index: usize, t_max: f64| {
+            let instance = &self.instances[index];
+            let prototype = &self.prototypes[instance.prototype];
+            let local = instance.to_object(ray);
+            match &prototype.geometry {
+                Geometry::Mesh(triangles) => prototype
+                    .bvh
+                    .closest_hit_within(&local, t_max, |i, t_max| {
+                        let triangle = triangles.start + i;
+                        self.mesh_triangle(triangle)
+                            .intersect(&local, acne_threshold, t_max)
+                            .map(|(t, uv)| (t, (triangle, uv)))
+                    })
+                    .map(|(t, (triangle, uv))| (t, (index, triangle, uv))),
+                Geometry::Shape { shape, .. } => shape
+                    .intersect(&local, acne_threshold, t_max)
+                    .map(|(t, uv)| (t, (index, 0, uv))),
+            }
+        };
+        let mut closest = self
+            .bvh
+            .closest_hit(ray, |i, t_max| intersect(self.bounded[i], t_max));
+        for &index in &self.unbounded {
+            let t_max = closest.as_ref().map_or(f64::INFINITY, |(t, _)| *t);
+            if let Some(hit) = intersect(index, t_max) {
+                closest = Some(hit);
+            }
+        }
+        closest.map(|(t, (instance, triangle, (u, v)))| Hit {
+            t,
+            instance,
+            triangle,
+            u,
+            v,
+        })
+    }
+
+    fn mesh_triangle(&self, triangle: usize) -> MeshTriangle<'_> {
+        MeshTriangle([0, 1, 2].map(|v| self.get_triangle_vertex(triangle, v)))
+    }
+
+    /// runs `f` on the object space surface a primitive of `instance` belongs to
+    fn with_shape<R>(
+        &self,
+        instance: usize,
+        triangle: usize,
+        f: impl FnOnce(&dyn Shape) -> R,
+    ) -> R {
+        match &self.prototypes[self.instances[instance].prototype].geometry {
+            Geometry::Mesh(_) => f(&self.mesh_triangle(triangle)),
+            Geometry::Shape { shape, .. } => f(shape.as_ref()),
+        }
     }
 
     pub fn get_triangle_vertex(&self, triangle: usize, v: u8) -> &Vector3<f64> {
@@ -121,7 +361,220 @@ impl Scene {
     pub fn get_triangel_mat(&self, triangle: usize) -> &Material {
         &self.materials[self.triangles[triangle].material]
     }
+    /// interpolated texture coordinates at barycentric (u, v), None if the face has none
+    pub fn get_texture_coords(&self, triangle: usize, u: f64, v: f64) -> Option<(f64, f64)> {
+        let [t0, t1, t2] = self.triangles[triangle].texture_coords?;
+        let (t0, t1, t2) = (
+            self.texture_coords[t0],
+            self.texture_coords[t1],
+            self.texture_coords[t2],
+        );
+        let w = 1.0 - u - v;
+        Some((
+            w * t0.0 + u * t1.0 + v * t2.0,
+            w * t0.1 + u * t1.1 + v * t2.1,
+        ))
+    }
+    /// material of `triangle` as seen through `instance`, which may override it
+    pub fn get_material(&self, instance: usize, triangle: usize) -> &Material {
+        let instance = &self.instances[instance];
+        let material = match &self.prototypes[instance.prototype].geometry {
+            Geometry::Mesh(_) => self.triangles[triangle].material,
+            Geometry::Shape { material, .. } => *material,
+        };
+        &self.materials[instance.material.unwrap_or(material)]
+    }
+    /// material of `triangle` with its texture maps looked up at barycentric (u, v)
+    pub fn get_surface_material(
+        &self,
+        instance: usize,
+        triangle: usize,
+        u: f64,
+        v: f64,
+    ) -> SurfaceMaterial<'_> {
+        let material = self.get_material(instance, triangle);
+        let mut result = SurfaceMaterial::new(material);
+        if !material.has_maps() {
+            return result;
+        }
+        let uv = match self.prototypes[self.instances[instance].prototype].geometry {
+            Geometry::Mesh(_) => self.get_texture_coords(triangle, u, v),
+            Geometry::Shape { .. } => Some((u, v)),
+        };
+        let Some(uv) = uv else {
+            return result;
+        };
+        if let Some(map) = material.diffuse_map {
+            result
+                .diffuse
+                .mul_element_wise(&self.textures[map].sample(uv));
+        }
+        if let Some(map) = material.emission_map {
+            result
+                .emission
+                .mul_element_wise(&self.textures[map].sample(uv));
+        }
+        if let Some(map) = material.specular_map {
+            result
+                .specular
+                .mul_element_wise(&self.textures[map].sample(uv));
+        }
+        if let Some(map) = material.specular_exp_map {
+            result.specular_exp *= self.textures[map].sample_scalar(uv);
+        }
+        result
+    }
+    pub fn get_triangle_area(&self, triangle: usize) -> f64 {
+        self.mesh_triangle(triangle).area()
+    }
+    pub fn get_world_triangle_area(&self, instance: usize, triangle: usize) -> f64 {
+        let transform = &self.instances[instance].transform;
+        let v0 = transform.point(self.get_triangle_vertex(triangle, 0));
+        let e0 = transform.point(self.get_triangle_vertex(triangle, 1)) - &v0;
+        let e1 = transform.point(self.get_triangle_vertex(triangle, 2)) - &v0;
+        0.5 * e0.cross(&e1).len()
+    }
+    /// exact for triangles, for analytic shapes only under uniform scaling
+    pub fn get_world_area(&self, instance: usize, triangle: usize) -> f64 {
+        match &self.prototypes[self.instances[instance].prototype].geometry {
+            Geometry::Mesh(_) => self.get_world_triangle_area(instance, triangle),
+            Geometry::Shape { shape, .. } => {
+                let det = self.instances[instance].transform.determinant();
+                shape.area() * det.abs().powf(2.0 / 3.0)
+            }
+        }
+    }
+    /// vertex normals interpolated across the triangle, then perturbed by the normal and
+    /// bump maps of its material, in world space
+    pub fn get_shading_normal(&self, hit: &Hit) -> Vector3<f64> {
+        let normal = self.get_object_shading_normal(hit);
+        let normal = self.instances[hit.instance].transform.normal(&normal);
+        let len = normal.len();
+        if len > 0.0 && len.is_finite() {
+            normal * (1.0 / len)
+        } else {
+            self.get_geometric_normal(hit)
+        }
+    }
+    fn get_object_shading_normal(&self, hit: &Hit) -> Vector3<f64> {
+        let prototype = &self.prototypes[self.instances[hit.instance].prototype];
+        if let Geometry::Shape { shape, .. } = &prototype.geometry {
+            return shape.normal((hit.u, hit.v));
+        }
+        let normals = &self.triangles[hit.triangle].point_normals;
+        let n = &self.point_normals[normals[0]] * (1.0 - hit.u - hit.v)
+            + &self.point_normals[normals[1]] * hit.u
+            + &self.point_normals[normals[2]] * hit.v;
+        let len = n.len();
+        let mut normal = if len > 0.0 && len.is_finite() {
+            n * (1.0 / len)
+        } else {
+            self.get_face_normal(hit.triangle).clone()
+        };
+
+        let material = self.get_material(hit.instance, hit.triangle);
+        if material.normal_map.is_none() && material.bump_map.is_none() {
+            return normal;
+        }
+        let (Some(uv), Some((dpdu, dpdv))) = (
+            self.get_texture_coords(hit.triangle, hit.u, hit.v),
+            self.get_uv_derivatives(hit.triangle),
+        ) else {
+            return normal;
+        };
+
+        if let (Some(map), Some(tangents)) =
+            (material.normal_map, self.triangles[hit.triangle].tangents)
+        {
+            let t = &self.tangents[tangents[0]] * (1.0 - hit.u - hit.v)
+                + &self.tangents[tangents[1]] * hit.u
+                + &self.tangents[tangents[2]] * hit.v;
+            // gram-schmidt, the bitangent follows v even on mirrored uvs
+            let tangent = &t - &normal * normal.dot(&t);
+            if tangent.len() > 1e-12 {
+                let tangent = tangent.normalized();
+                let mut bitangent = tangent.cross(&normal);
+                if bitangent.dot(&dpdv) < 0.0 {
+                    bitangent = -bitangent;
+                }
+                let texel = self.textures[map].sample(uv);
+                let strength = material.normal_strength;
+                let mapped = tangent * ((2.0 * texel.x() - 1.0) * strength)
+                    + bitangent * ((2.0 * texel.y() - 1.0) * strength)
+                    + &normal * (2.0 * texel.z() - 1.0);
+                if mapped.len() > 1e-12 {
+                    normal = mapped.normalized();
+                }
+            }
+        }
+
+        // https://www.pbr-book.org/4ed/Textures_and_Materials/Material_Interface_and_Implementations#NormalorBumpMapping
+        if let Some(map) = material.bump_map {
+            let texture = &self.textures[map];
+            let height = |uv: (f64, f64)| texture.sample_scalar(uv) * material.bump_height;
+            // central differences one texel wide
+            let (width, height_px) = texture.shape();
+            let (du, dv) = (0.5 / width as f64, 0.5 / height_px as f64);
+            let dhdu = (height((uv.0 + du, uv.1)) - height((uv.0 - du, uv.1))) / (2.0 * du);
+            let dhdv = (height((uv.0, uv.1 + dv)) - height((uv.0, uv.1 - dv))) / (2.0 * dv);
+            // displace the tangent plane of the current normal rather than the flat face
+            let dpdu = &dpdu - &normal * normal.dot(&dpdu) + &normal * dhdu;
+            let dpdv = &dpdv - &normal * normal.dot(&dpdv) + &normal * dhdv;
+            let bumped = dpdu.cross(&dpdv);
+            let len = bumped.len();
+            if len > 1e-12 && len.is_finite() {
+                let bumped = bumped * (1.0 / len);
+                normal = if bumped.dot(&normal) < 0.0 {
+                    -bumped
+                } else {
+                    bumped
+                };
+            }
+        }
+        normal
+    }
+    /// partial derivatives of the position with respect to the texture coordinates
+    pub fn get_uv_derivatives(&self, triangle: usize) -> Option<(Vector3<f64>, Vector3<f64>)> {
+        let [t0, t1, t2] = self.triangles[triangle]
+            .texture_coords?
+            .map(|t| self.texture_coords[t]);
+        let v0 = self.get_triangle_vertex(triangle, 0);
+        let e0 = self.get_triangle_vertex(triangle, 1) - v0;
+        let e1 = self.get_triangle_vertex(triangle, 2) - v0;
+        let (du0, dv0) = (t1.0 - t0.0, t1.1 - t0.1);
+        let (du1, dv1) = (t2.0 - t0.0, t2.1 - t0.1);
+        let det = du0 * dv1 - du1 * dv0;
+        if det.abs() <= f64::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        Some((
+            (&e0 * dv1 - &e1 * dv0) * inv_det,
+            (&e1 * du0 - &e0 * du1) * inv_det,
+        ))
+    }
     pub fn get_face_normal(&self, triangle: usize) -> &Vector3<f64> {
         &self.face_normals[self.triangles[triangle].face_normal]
     }
+    pub fn get_world_face_normal(&self, instance: usize, triangle: usize) -> Vector3<f64> {
+        self.to_world_normal(instance, self.get_face_normal(triangle))
+    }
+    /// normal of the surface itself at the hit point, in world space
+    pub fn get_geometric_normal(&self, hit: &Hit) -> Vector3<f64> {
+        match &self.prototypes[self.instances[hit.instance].prototype].geometry {
+            Geometry::Mesh(_) => self.get_world_face_normal(hit.instance, hit.triangle),
+            Geometry::Shape { shape, .. } => {
+                self.to_world_normal(hit.instance, &shape.normal((hit.u, hit.v)))
+            }
+        }
+    }
+    fn to_world_normal(&self, instance: usize, normal: &Vector3<f64>) -> Vector3<f64> {
+        let normal = self.instances[instance].transform.normal(normal);
+        let len = normal.len();
+        if len > 0.0 && len.is_finite() {
+            normal * (1.0 / len)
+        } else {
+            normal
+        }
+    }
 }
@@ -0,0 +1,339 @@
+use std::f64::consts::{PI, TAU};
+use std::fmt::Debug;
+
+use super::bvh::Aabb;
+use crate::{ray::Ray, vector::Vector3};
+
+/// a surface in object space. `uv` parameterizes it, doubling as texture coordinates
+pub trait Shape: Debug + Send + Sync {
+    /// may be infinite for unbounded shapes
+    fn bounds(&self) -> Aabb;
+    /// distance and surface coordinates of the closest hit in (`t_min`, `t_max`)
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))>;
+    fn point(&self, uv: (f64, f64)) -> Vector3<f64>;
+    /// unit length, pointing outwards
+    fn normal(&self, uv: (f64, f64)) -> Vector3<f64>;
+    fn area(&self) -> f64;
+    /// surface coordinates of a point distributed uniformly by area, `u` is uniform in
+    /// the unit square
+    fn sample(&self, u: (f64, f64)) -> (f64, f64);
+}
+
+/// solutions of a t^2 + b t + c = 0 in ascending order
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+    // https://en.wikipedia.org/wiki/Loss_of_significance#Instability_of_the_quadratic_equation
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = (q / a, c / q);
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// angle around z in [0, 1)
+fn azimuth(x: f64, y: f64) -> f64 {
+    let phi = y.atan2(x) / TAU;
+    if phi < 0.0 {
+        phi + 1.0
+    } else {
+        phi
+    }
+}
+
+/// where the ray crosses z = 0
+fn intersect_xy_plane(ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Vector3<f64>)> {
+    let t = -ray.orig().z() / ray.dir().z();
+    (t > t_min && t < t_max).then(|| (t, ray.point_at(t)))
+}
+
+/// a triangle of a mesh, `uv` are the barycentric weights of the second and third vertex
+#[derive(Debug, Clone, Copy)]
+pub struct MeshTriangle<'a>(pub [&'a Vector3<f64>; 3]);
+
+impl Shape for MeshTriangle<'_> {
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.0)
+    }
+
+    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let [v0, v1, v2] = self.0;
+
+        let e0 = v1 - v0;
+        let e1 = v2 - v0;
+
+        let ray_x_e1 = ray.dir().cross(&e1);
+        let determinant = ray_x_e1.dot(&e0);
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let inverse_det = 1.0 / determinant;
+        let a = ray.orig() - v0;
+        let u = inverse_det * a.dot(&ray_x_e1);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let a_x_e0 = a.cross(&e0);
+        let v = inverse_det * ray.dir().dot(&a_x_e0);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = inverse_det * e1.dot(&a_x_e0);
+        (t > t_min && t < t_max).then_some((t, (u, v)))
+    }
+
+    fn point(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        let [v0, v1, v2] = self.0;
+        v0 * (1.0 - u - v) + v1 * u + v2 * v
+    }
+
+    fn normal(&self, _: (f64, f64)) -> Vector3<f64> {
+        let [v0, v1, v2] = self.0;
+        (v2 - v0).cross(&(v1 - v0)).normalized()
+    }
+
+    fn area(&self) -> f64 {
+        let [v0, v1, v2] = self.0;
+        0.5 * (v1 - v0).cross(&(v2 - v0)).len()
+    }
+
+    // https://www.pbr-book.org/4ed/Shapes/Triangles#Sampling
+    fn sample(&self, (u0, u1): (f64, f64)) -> (f64, f64) {
+        let r0 = u0.sqrt();
+        (r0 * (1.0 - u1), r0 * u1)
+    }
+}
+
+/// centered on the origin, u goes around z and v from the bottom to the top
+#[derive(Debug, Clone)]
+pub struct Sphere {
+    pub radius: f64,
+}
+
+impl Sphere {
+    pub fn new(radius: f64) -> Sphere {
+        Sphere { radius }
+    }
+}
+
+impl Shape for Sphere {
+    fn bounds(&self) -> Aabb {
+        let r = self.radius;
+        Aabb::from_points([&Vector3::new(-r, -r, -r), &Vector3::new(r, r, r)])
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let (o, d) = (ray.orig(), ray.dir());
+        let (t0, t1) = solve_quadratic(
+            d.dot(d),
+            2.0 * o.dot(d),
+            o.dot(o) - self.radius * self.radius,
+        )?;
+        let t = [t0, t1].into_iter().find(|&t| t > t_min && t < t_max)?;
+        let p = ray.point_at(t);
+        let cos_theta = (p.z() / self.radius).clamp(-1.0, 1.0);
+        Some((t, (azimuth(p.x(), p.y()), 1.0 - cos_theta.acos() / PI)))
+    }
+
+    fn point(&self, uv: (f64, f64)) -> Vector3<f64> {
+        self.normal(uv) * self.radius
+    }
+
+    fn normal(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        let (phi, theta) = (u * TAU, (1.0 - v) * PI);
+        Vector3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        )
+    }
+
+    fn area(&self) -> f64 {
+        2.0 * TAU * self.radius * self.radius
+    }
+
+    // archimedes, z is uniform on a sphere
+    fn sample(&self, (u0, u1): (f64, f64)) -> (f64, f64) {
+        (u0, 1.0 - (1.0 - 2.0 * u1).clamp(-1.0, 1.0).acos() / PI)
+    }
+}
+
+/// the xy plane, facing +z. uv are the x and y coordinates, so textures repeat every unit.
+/// its area is infinite, so it never becomes a light
+#[derive(Debug, Clone, Default)]
+pub struct Plane;
+
+impl Shape for Plane {
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points([
+            &Vector3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, 0.0),
+            &Vector3::new(f64::INFINITY, f64::INFINITY, 0.0),
+        ])
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let (t, p) = intersect_xy_plane(ray, t_min, t_max)?;
+        Some((t, (p.x(), p.y())))
+    }
+
+    fn point(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        Vector3::new(u, v, 0.0)
+    }
+
+    fn normal(&self, _: (f64, f64)) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 1.0)
+    }
+
+    fn area(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    fn sample(&self, _: (f64, f64)) -> (f64, f64) {
+        (0.0, 0.0)
+    }
+}
+
+/// in the xy plane around the origin, facing +z. u goes around z and v outwards
+#[derive(Debug, Clone)]
+pub struct Disk {
+    pub radius: f64,
+}
+
+impl Disk {
+    pub fn new(radius: f64) -> Disk {
+        Disk { radius }
+    }
+}
+
+impl Shape for Disk {
+    fn bounds(&self) -> Aabb {
+        let r = self.radius;
+        Aabb::from_points([&Vector3::new(-r, -r, 0.0), &Vector3::new(r, r, 0.0)])
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let (t, p) = intersect_xy_plane(ray, t_min, t_max)?;
+        let r = p.x().hypot(p.y());
+        (r <= self.radius).then(|| (t, (azimuth(p.x(), p.y()), r / self.radius)))
+    }
+
+    fn point(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        let (phi, r) = (u * TAU, v * self.radius);
+        Vector3::new(r * phi.cos(), r * phi.sin(), 0.0)
+    }
+
+    fn normal(&self, _: (f64, f64)) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 1.0)
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    fn sample(&self, (u0, u1): (f64, f64)) -> (f64, f64) {
+        (u0, u1.sqrt())
+    }
+}
+
+/// open tube of the given radius around z, from z = 0 up to `height`. u goes around z
+/// and v upwards
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    pub radius: f64,
+    pub height: f64,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64) -> Cylinder {
+        Cylinder { radius, height }
+    }
+}
+
+impl Shape for Cylinder {
+    fn bounds(&self) -> Aabb {
+        let r = self.radius;
+        Aabb::from_points([&Vector3::new(-r, -r, 0.0), &Vector3::new(r, r, self.height)])
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let (o, d) = (ray.orig(), ray.dir());
+        let (t0, t1) = solve_quadratic(
+            d.x() * d.x() + d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.y() * d.y()),
+            o.x() * o.x() + o.y() * o.y() - self.radius * self.radius,
+        )?;
+        [t0, t1].into_iter().find_map(|t| {
+            let p = ray.point_at(t);
+            (t > t_min && t < t_max && (0.0..=self.height).contains(&p.z()))
+                .then(|| (t, (azimuth(p.x(), p.y()), p.z() / self.height)))
+        })
+    }
+
+    fn point(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        let phi = u * TAU;
+        Vector3::new(
+            self.radius * phi.cos(),
+            self.radius * phi.sin(),
+            v * self.height,
+        )
+    }
+
+    fn normal(&self, (u, _): (f64, f64)) -> Vector3<f64> {
+        let phi = u * TAU;
+        Vector3::new(phi.cos(), phi.sin(), 0.0)
+    }
+
+    fn area(&self) -> f64 {
+        TAU * self.radius * self.height
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        u
+    }
+}
+
+/// `width` along x and `height` along y, centered on the origin and facing +z
+#[derive(Debug, Clone)]
+pub struct Rectangle {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rectangle {
+    pub fn new(width: f64, height: f64) -> Rectangle {
+        Rectangle { width, height }
+    }
+}
+
+impl Shape for Rectangle {
+    fn bounds(&self) -> Aabb {
+        let (w, h) = (0.5 * self.width, 0.5 * self.height);
+        Aabb::from_points([&Vector3::new(-w, -h, 0.0), &Vector3::new(w, h, 0.0)])
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, (f64, f64))> {
+        let (t, p) = intersect_xy_plane(ray, t_min, t_max)?;
+        let uv = (p.x() / self.width + 0.5, p.y() / self.height + 0.5);
+        ((0.0..=1.0).contains(&uv.0) && (0.0..=1.0).contains(&uv.1)).then_some((t, uv))
+    }
+
+    fn point(&self, (u, v): (f64, f64)) -> Vector3<f64> {
+        Vector3::new((u - 0.5) * self.width, (v - 0.5) * self.height, 0.0)
+    }
+
+    fn normal(&self, _: (f64, f64)) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 1.0)
+    }
+
+    fn area(&self) -> f64 {
+        self.width * self.height
+    }
+
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        u
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashMap, ops::Range};
+
+use super::{CameraDesc, MaterialDesc, SceneFile, SceneFileError, ShapeKind, TransformDesc};
+use crate::{
+    camera::Camera,
+    image::Image,
+    obj::LoadOptions,
+    renderer::Renderer,
+    scene::{
+        instance::Instance,
+        mesh::Material,
+        shape::{Cylinder, Disk, Plane, Rectangle, Sphere},
+        Scene,
+    },
+    vector::{Quaternion, Transform, Vector3},
+};
+
+fn vector([x, y, z]: [f64; 3]) -> Vector3<f64> {
+    Vector3::new(x, y, z)
+}
+
+impl MaterialDesc {
+    pub fn to_material(&self) -> Material {
+        Material {
+            diffuse: vector(self.diffuse),
+            specular: vector(self.specular),
+            specular_exp: self.specular_exp,
+            emission: vector(self.emission),
+            opacity: self.opacity,
+            density: self.ior,
+            illum: self.illum,
+            ..Default::default()
+        }
+    }
+}
+
+impl ShapeKind {
+    /// adds the shape as a prototype drawn with `material`
+    pub fn add_to(&self, scene: &mut Scene, material: usize) -> usize {
+        match *self {
+            ShapeKind::Sphere { radius } => scene.add_shape(Sphere::new(radius), material),
+            ShapeKind::Plane => scene.add_shape(Plane, material),
+            ShapeKind::Disk { radius } => scene.add_shape(Disk::new(radius), material),
+            ShapeKind::Cylinder { radius, height } => {
+                scene.add_shape(Cylinder::new(radius, height), material)
+            }
+            ShapeKind::Rectangle { width, height } => {
+                scene.add_shape(Rectangle::new(width, height), material)
+            }
+        }
+    }
+}
+
+impl TransformDesc {
+    /// None if it cannot be inverted
+    pub fn to_transform(&self) -> Option<Transform> {
+        let axis = vector(self.axis);
+        if self.degrees != 0.0 && axis.len() == 0.0 {
+            return None;
+        }
+        let rotation = Quaternion::from_axis_angle(&axis, self.degrees.to_radians());
+        Some(
+            Transform::scale(&vector(self.scale))?
+                .then(&Transform::rotate(&rotation))
+                .then(&Transform::translate(&vector(self.translate))),
+        )
+    }
+}
+
+impl CameraDesc {
+    /// everything `Camera::new` or `Camera::set_lens` would panic on. an `up` along the
+    /// view direction is fine, the camera picks another one
+    pub fn validate(&self) -> Result<(), SceneFileError> {
+        let invalid = |reason: String| Err(SceneFileError::InvalidCamera { reason });
+        let [width, height] = self.resolution;
+        if width == 0 || height == 0 {
+            return invalid(format!("resolution {width}x{height} has no pixels"));
+        }
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return invalid(format!("vfov {} is outside of (0, 180)", self.vfov));
+        }
+        let view = vector(self.target) - vector(self.eye);
+        if view.len() == 0.0 {
+            return invalid("eye and target are the same point".to_string());
+        }
+        if let Some(distance) = self.focus_distance {
+            if !(distance > 0.0 && distance.is_finite()) {
+                return invalid(format!("focus distance {distance} is not positive"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_camera(&self) -> Result<Camera, SceneFileError> {
+        self.validate()?;
+        let mut camera = Camera::new(
+            vector(self.eye),
+            vector(self.target),
+            vector(self.up),
+            self.vfov,
+            (self.resolution[0], self.resolution[1]),
+        );
+        let focus_distance = self.focus_distance.unwrap_or(camera.focus_distance());
+        camera.set_lens(self.aperture_radius, focus_distance);
+        camera.set_blades(self.blades);
+        Ok(camera)
+    }
+}
+
+impl SceneFile {
+    /// loads every mesh and places every instance and light. the camera is checked
+    /// first so that a broken file fails before any mesh is loaded
+    pub fn build_scene(&self) -> Result<Scene, SceneFileError> {
+        self.camera.validate()?;
+        let mut scene = Scene::default();
+
+        let mut materials = HashMap::new();
+        for desc in &self.materials {
+            if materials
+                .insert(desc.name.clone(), scene.materials.len())
+                .is_some()
+            {
+                return Err(SceneFileError::DuplicateName {
+                    kind: "material",
+                    name: desc.name.clone(),
+                });
+            }
+            scene.materials.push(desc.to_material());
+        }
+        let material = |name: &String| {
+            materials
+                .get(name)
+                .copied()
+                .ok_or_else(|| SceneFileError::UnknownName {
+                    kind: "material",
+                    name: name.clone(),
+                })
+        };
+
+        let mut objects: HashMap<String, Range<usize>> = HashMap::new();
+        let mut add_object = |name: &String, prototypes: Range<usize>| match objects
+            .insert(name.clone(), prototypes)
+        {
+            Some(_) => Err(SceneFileError::DuplicateName {
+                kind: "object",
+                name: name.clone(),
+            }),
+            None => Ok(()),
+        };
+        for desc in &self.meshes {
+            let options = LoadOptions {
+                scale: desc.scale,
+                y_up: desc.y_up,
+                ..Default::default()
+            };
+            let prototypes = scene.add_obj(&self.resolve(&desc.path), &options)?;
+            add_object(&desc.name, prototypes)?;
+        }
+        for desc in &self.shapes {
+            let prototype = desc.shape.add_to(&mut scene, material(&desc.material)?);
+            add_object(&desc.name, prototype..prototype + 1)?;
+        }
+
+        for desc in &self.instances {
+            let prototypes =
+                objects
+                    .get(&desc.object)
+                    .ok_or_else(|| SceneFileError::UnknownName {
+                        kind: "object",
+                        name: desc.object.clone(),
+                    })?;
+            let transform =
+                desc.transform
+                    .to_transform()
+                    .ok_or_else(|| SceneFileError::InvalidTransform {
+                        object: desc.object.clone(),
+                    })?;
+            let material = desc.material.as_ref().map(material).transpose()?;
+            for prototype in prototypes.clone() {
+                let mut instance = Instance::new(prototype, transform);
+                instance.material = material;
+                scene.add_instance(instance);
+            }
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let transform =
+                light
+                    .transform
+                    .to_transform()
+                    .ok_or_else(|| SceneFileError::InvalidTransform {
+                        object: format!("light {index}"),
+                    })?;
+            let material = scene.materials.len();
+            scene.materials.push(Material {
+                emission: vector(light.emission),
+                ..Default::default()
+            });
+            let prototype = light.shape.add_to(&mut scene, material);
+            scene.add_instance(Instance::new(prototype, transform));
+        }
+
+        scene.build();
+        Ok(scene)
+    }
+
+    /// a renderer with the camera, settings and scene of the file
+    pub fn renderer(&self) -> Result<Renderer, SceneFileError> {
+        self.renderer_for(self.build_scene()?)
+    }
+
+    /// `renderer` for a scene that was already built, such as one from `build_scene`
+    pub fn renderer_for(&self, scene: Scene) -> Result<Renderer, SceneFileError> {
+        let mut renderer = Renderer::new(self.camera.to_camera()?, self.render.clone())?;
+        renderer.set_scene(scene);
+        Ok(renderer)
+    }
+
+    /// writes `image` to every path in `output`, and the render settings if asked to
+    pub fn write_outputs(&self, image: &Image) -> Result<(), SceneFileError> {
+        let output = &self.output;
+        let write = |path: &Option<String>,
+                     write: &dyn Fn(&str) -> Result<(), std::io::Error>|
+         -> Result<(), SceneFileError> {
+            let Some(path) = path else {
+                return Ok(());
+            };
+            let file = self.resolve(path);
+            write(&file).map_err(|error| SceneFileError::Io { file, error })
+        };
+        write(&output.png, &|p| {
+            image.write_to_png_with(p, &output.tone_mapping)
+        })?;
+        write(&output.hdr, &|p| image.write_to_hdr(p))?;
+        write(&output.pfm, &|p| image.write_to_pfm(p))?;
+        write(&output.exr, &|p| {
+            image.write_to_exr(p, output.exr_pixel_type)
+        })?;
+        let settings = match &output.settings {
+            Some(_) => self
+                .render
+                .to_toml()
+                .map_err(|message| SceneFileError::Serialize { message })?,
+            None => String::new(),
+        };
+        write(&output.settings, &|p| std::fs::write(p, &settings))
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+
+use crate::{obj::ObjError, renderer::SettingsError};
+
+/// everything that can go wrong while reading, writing or setting up a scene file
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// the file could not be read or written
+    Io { file: String, error: std::io::Error },
+    /// not valid toml, or not a valid scene. the message carries the line and column
+    Parse { file: String, message: String },
+    /// the description cannot be written as toml, such as a seed above `i64::MAX`
+    Serialize { message: String },
+    /// a referenced obj file failed to load
+    Obj(ObjError),
+    /// a name that no material or object of that kind was given
+    UnknownName { kind: &'static str, name: String },
+    /// two materials or objects with the same name
+    DuplicateName { kind: &'static str, name: String },
+    /// a transform that cannot be inverted, or a rotation around a zero axis
+    InvalidTransform { object: String },
+    /// a camera that cannot see anything, such as one without pixels
+    InvalidCamera { reason: String },
+    /// render settings the renderer does not accept
+    Settings(SettingsError),
+}
+
+impl From<ObjError> for SceneFileError {
+    fn from(error: ObjError) -> SceneFileError {
+        SceneFileError::Obj(error)
+    }
+}
+
+impl From<SettingsError> for SceneFileError {
+    fn from(error: SettingsError) -> SceneFileError {
+        SceneFileError::Settings(error)
+    }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io { file, error } => write!(f, "{file}: {error}"),
+            SceneFileError::Parse { file, message } => write!(f, "{file}: {message}"),
+            SceneFileError::Serialize { message } => write!(f, "{message}"),
+            SceneFileError::Obj(error) => write!(f, "{error}"),
+            SceneFileError::UnknownName { kind, name } => write!(f, "unknown {kind} `{name}`"),
+            SceneFileError::DuplicateName { kind, name } => {
+                write!(f, "{kind} `{name}` is defined more than once")
+            }
+            SceneFileError::InvalidTransform { object } => {
+                write!(f, "the transform of `{object}` is degenerate")
+            }
+            SceneFileError::InvalidCamera { reason } => write!(f, "invalid camera, {reason}"),
+            SceneFileError::Settings(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io { error, .. } => Some(error),
+            SceneFileError::Obj(error) => Some(error),
+            SceneFileError::Settings(error) => Some(error),
+            _ => None,
+        }
+    }
+}
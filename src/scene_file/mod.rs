@@ -0,0 +1,259 @@
+mod build;
+mod error;
+
+pub use error::SceneFileError;
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    image::{ExrPixelType, ToneMapping},
+    obj::PIXELS_PER_METER,
+    renderer::RenderSettings,
+};
+
+/// everything needed to render an image, stored as toml. paths are relative to the
+/// folder the file is in
+/// ```toml
+/// [camera]
+/// eye = [0.0, -1500.0, 160.0]
+/// target = [0.0, -1400.0, 155.0]
+///
+/// [[meshes]]
+/// name = "knight"
+/// path = "lightknight.obj"
+///
+/// [[instances]]
+/// object = "knight"
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraDesc,
+    pub render: RenderSettings,
+    pub output: OutputDesc,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<MaterialDesc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<MeshDesc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shapes: Vec<ShapeDesc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceDesc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDesc>,
+    /// where relative paths start from, the folder of the file it was loaded from
+    #[serde(skip)]
+    pub folder: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDesc {
+    pub eye: [f64; 3],
+    pub target: [f64; 3],
+    pub up: [f64; 3],
+    /// vertical, in degrees
+    pub vfov: f64,
+    pub resolution: [usize; 2],
+    /// 0 for a pinhole
+    pub aperture_radius: f64,
+    /// defaults to the distance to `target`
+    pub focus_distance: Option<f64>,
+    /// 0 for a round aperture
+    pub blades: u32,
+}
+
+impl Default for CameraDesc {
+    fn default() -> CameraDesc {
+        CameraDesc {
+            eye: [0.0, -1000.0, 0.0],
+            target: [0.0, 0.0, 0.0],
+            up: [0.0, 0.0, 1.0],
+            vfov: 40.0,
+            resolution: [640, 360],
+            aperture_radius: 0.0,
+            focus_distance: None,
+            blades: 0,
+        }
+    }
+}
+
+/// images written after rendering, any number of them
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputDesc {
+    pub png: Option<String>,
+    pub hdr: Option<String>,
+    pub pfm: Option<String>,
+    pub exr: Option<String>,
+    /// the render settings as toml, to tell later how the images were made
+    pub settings: Option<String>,
+    pub exr_pixel_type: ExrPixelType,
+    /// only applies to `png`
+    pub tone_mapping: ToneMapping,
+}
+
+impl OutputDesc {
+    /// whether any image is written, the settings alone don't count
+    pub fn has_images(&self) -> bool {
+        [&self.png, &self.hdr, &self.pfm, &self.exr]
+            .iter()
+            .any(|path| path.is_some())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaterialDesc {
+    pub name: String,
+    pub diffuse: [f64; 3],
+    pub specular: [f64; 3],
+    pub specular_exp: f64,
+    pub emission: [f64; 3],
+    pub opacity: f64,
+    pub ior: f64,
+    /// the mtl illumination model, 4, 6 and 7 refract
+    pub illum: u8,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> MaterialDesc {
+        MaterialDesc {
+            name: String::new(),
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            specular_exp: 0.0,
+            emission: [0.0, 0.0, 0.0],
+            opacity: 1.0,
+            ior: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+/// an obj file, every `o` group in it is placed together by the instances of `name`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshDesc {
+    pub name: String,
+    pub path: String,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub y_up: bool,
+}
+
+fn default_scale() -> f64 {
+    PIXELS_PER_METER
+}
+
+/// the analytic shapes of `scene::shape`, sizes are in scene units
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ShapeKind {
+    Sphere { radius: f64 },
+    Plane,
+    Disk { radius: f64 },
+    Cylinder { radius: f64, height: f64 },
+    Rectangle { width: f64, height: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShapeDesc {
+    pub name: String,
+    pub material: String,
+    pub shape: ShapeKind,
+}
+
+/// scaled, then rotated, then moved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransformDesc {
+    pub scale: [f64; 3],
+    pub axis: [f64; 3],
+    /// around `axis`
+    pub degrees: f64,
+    pub translate: [f64; 3],
+}
+
+impl Default for TransformDesc {
+    fn default() -> TransformDesc {
+        TransformDesc {
+            scale: [1.0, 1.0, 1.0],
+            axis: [0.0, 0.0, 1.0],
+            degrees: 0.0,
+            translate: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// places a mesh or shape by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceDesc {
+    pub object: String,
+    /// replaces the materials of every face
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub transform: TransformDesc,
+}
+
+/// a shape that glows, without a material of its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LightDesc {
+    pub emission: [f64; 3],
+    pub shape: ShapeKind,
+    #[serde(default)]
+    pub transform: TransformDesc,
+}
+
+impl SceneFile {
+    pub fn load(path: &str) -> Result<SceneFile, SceneFileError> {
+        let text = std::fs::read_to_string(path).map_err(|error| SceneFileError::Io {
+            file: path.to_string(),
+            error,
+        })?;
+        let mut result = SceneFile::from_toml(&text).map_err(|message| SceneFileError::Parse {
+            file: path.to_string(),
+            message,
+        })?;
+        result.folder = Path::new(path)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(result)
+    }
+
+    /// relative paths are kept as they are, so the file can move along with its assets
+    pub fn save(&self, path: &str) -> Result<(), SceneFileError> {
+        std::fs::write(path, self.to_toml()?).map_err(|error| SceneFileError::Io {
+            file: path.to_string(),
+            error,
+        })
+    }
+
+    /// parses without touching the file system, paths are relative to the working
+    /// directory
+    pub fn from_toml(text: &str) -> Result<SceneFile, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml(&self) -> Result<String, SceneFileError> {
+        toml::to_string(self).map_err(|e| SceneFileError::Serialize {
+            message: e.to_string(),
+        })
+    }
+
+    /// `path` as seen from the working directory
+    pub fn resolve(&self, path: &str) -> String {
+        Path::new(&self.folder)
+            .join(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs::File, io::BufReader};
+
+use crate::vector::Vector3;
+
+/// what happens to texture coordinates outside of [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WrapMode {
+    /// the texture tiles, the default for mtl files
+    #[default]
+    Repeat,
+    /// edge texels are stretched outwards, `-clamp on` in mtl files
+    Clamp,
+    /// the texture tiles, flipping every other copy so the seams match
+    Mirror,
+}
+
+impl WrapMode {
+    /// texel index for a possibly out of range `i`
+    fn wrap(self, i: isize, size: usize) -> usize {
+        let size = size as isize;
+        match self {
+            WrapMode::Repeat => i.rem_euclid(size) as usize,
+            WrapMode::Clamp => i.clamp(0, size - 1) as usize,
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                (if i < size { i } else { 2 * size - 1 - i }) as usize
+            }
+        }
+    }
+}
+
+/// srgb electro-optical transfer function, the inverse of `image::srgb_oetf`
+fn srgb_eotf(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// rgb image sampled with texture coordinates, (0, 0) is the lower left corner like in
+/// obj files
+#[derive(Debug, Clone)]
+pub struct Texture {
+    /// row by row from the top, like the png it came from
+    texels: Vec<Vector3<f64>>,
+    shape: (usize, usize),
+    wrap: WrapMode,
+}
+
+impl Texture {
+    pub fn new(texels: Vec<Vector3<f64>>, shape: (usize, usize), wrap: WrapMode) -> Texture {
+        assert_eq!(
+            texels.len(),
+            shape.0 * shape.1,
+            "texels do not match the shape"
+        );
+        assert!(!texels.is_empty(), "empty texture");
+        Texture {
+            texels,
+            shape,
+            wrap,
+        }
+    }
+
+    /// decodes a png of any bit depth and color type, `srgb` converts the values to linear
+    /// which is what color maps need, data like exponents is stored as is
+    pub fn from_png(path: &str, srgb: bool, wrap: WrapMode) -> Result<Texture, std::io::Error> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+
+        let channels = info.color_type.samples();
+        let decode = |b: u8| {
+            let x = b as f64 / 255.0;
+            if srgb {
+                srgb_eotf(x)
+            } else {
+                x
+            }
+        };
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut texels = Vec::with_capacity(width * height);
+        for row in buf.chunks(info.line_size).take(height) {
+            for px in row.chunks(channels).take(width) {
+                // gray stays gray, alpha is ignored
+                texels.push(if channels < 3 {
+                    let g = decode(px[0]);
+                    Vector3::new(g, g, g)
+                } else {
+                    Vector3::new(decode(px[0]), decode(px[1]), decode(px[2]))
+                });
+            }
+        }
+        if texels.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{path} has no pixels"),
+            ));
+        }
+        Ok(Texture::new(texels, (width, height), wrap))
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    fn texel(&self, x: isize, y: isize) -> &Vector3<f64> {
+        let x = self.wrap.wrap(x, self.shape.0);
+        let y = self.wrap.wrap(y, self.shape.1);
+        &self.texels[x + y * self.shape.0]
+    }
+
+    /// bilinearly filtered color at texture coordinates `uv`
+    pub fn sample(&self, uv: (f64, f64)) -> Vector3<f64> {
+        // texel centers are at half integers, v goes up while rows go down
+        let x = uv.0 * self.shape.0 as f64 - 0.5;
+        let y = (1.0 - uv.1) * self.shape.1 as f64 - 0.5;
+        if !x.is_finite() || !y.is_finite() {
+            return self.texels[0].clone();
+        }
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        self.texel(x0, y0) * ((1.0 - fx) * (1.0 - fy))
+            + self.texel(x0 + 1, y0) * (fx * (1.0 - fy))
+            + self.texel(x0, y0 + 1) * ((1.0 - fx) * fy)
+            + self.texel(x0 + 1, y0 + 1) * (fx * fy)
+    }
+
+    /// single channel lookup for scalar maps
+    pub fn sample_scalar(&self, uv: (f64, f64)) -> f64 {
+        self.sample(uv).x()
+    }
+}
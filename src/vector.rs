@@ -83,6 +83,7 @@ impl<T: VecElem> Vector3<T> {
 }
 
 impl<T: SignedVecElem> Vector3<T> {
+    /// `rhs x self`, the operands are the other way around than in `self x rhs`
     pub fn cross(&self, rhs: &Vector3<T>) -> Vector3<T> {
         Vector3::new(
             -(self[1] * rhs[2] - self[2] * rhs[1]),
@@ -103,6 +104,14 @@ impl Vector3<f64> {
             *x /= len;
         }
     }
+
+    pub fn normalized(&self) -> Vector3<f64> {
+        self * (1.0 / self.len())
+    }
+
+    pub fn max_elem(&self) -> f64 {
+        self.v[0].max(self.v[1]).max(self.v[2])
+    }
 }
 
 macro_rules! vec_add {
@@ -180,6 +189,20 @@ impl<T: SignedVecElem> Sub<&Vector3<T>> for &Vector3<T> {
     vec_sub!(&Vector3<T>);
 }
 
+impl<T: SignedVecElem> Neg for Vector3<T> {
+    type Output = Vector3<T>;
+    fn neg(self) -> Self::Output {
+        Vector3::new(-self[0], -self[1], -self[2])
+    }
+}
+
+impl<T: SignedVecElem> Neg for &Vector3<T> {
+    type Output = Vector3<T>;
+    fn neg(self) -> Self::Output {
+        Vector3::new(-self[0], -self[1], -self[2])
+    }
+}
+
 impl<T: VecElem> Mul<T> for Vector3<T> {
     vec_mul!();
 }
@@ -211,3 +234,410 @@ impl<T: VecElem> AddAssign<&Vector3<T>> for Vector3<T> {
 impl<T: VecElem> AddAssign<&Vector3<T>> for &mut Vector3<T> {
     vec_add_assign!(&Vector3<T>);
 }
+
+/// row major 4x4 matrix acting on column vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4<T> {
+    m: [[T; 4]; 4],
+}
+
+impl<T: VecElem> Index<(usize, usize)> for Matrix4<T> {
+    type Output = T;
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.m[index.0][index.1]
+    }
+}
+
+impl<T: VecElem> IndexMut<(usize, usize)> for Matrix4<T> {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.m[index.0][index.1]
+    }
+}
+
+impl<T: VecElem> Matrix4<T> {
+    pub fn new(m: [[T; 4]; 4]) -> Matrix4<T> {
+        Matrix4 { m }
+    }
+
+    pub fn rows(&self) -> &[[T; 4]; 4] {
+        &self.m
+    }
+
+    pub fn transpose(&self) -> Matrix4<T> {
+        Matrix4::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.m[j][i])
+        }))
+    }
+
+    /// `p` with an implicit w of 1, the bottom row is assumed to be 0 0 0 1
+    pub fn transform_point(&self, p: &Vector3<T>) -> Vector3<T> {
+        let row = |i: usize| {
+            self.m[i][0] * p[0] + self.m[i][1] * p[1] + self.m[i][2] * p[2] + self.m[i][3]
+        };
+        Vector3::new(row(0), row(1), row(2))
+    }
+
+    /// `v` with an implicit w of 0, translations don't affect it
+    pub fn transform_direction(&self, v: &Vector3<T>) -> Vector3<T> {
+        let row = |i: usize| self.m[i][0] * v[0] + self.m[i][1] * v[1] + self.m[i][2] * v[2];
+        Vector3::new(row(0), row(1), row(2))
+    }
+}
+
+impl<T: VecElem> Mul for Matrix4<T> {
+    type Output = Matrix4<T>;
+    fn mul(self, rhs: Matrix4<T>) -> Self::Output {
+        Matrix4::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let mut sum = T::default();
+                for k in 0..4 {
+                    sum += self.m[i][k] * rhs.m[k][j];
+                }
+                sum
+            })
+        }))
+    }
+}
+
+impl Default for Matrix4<f64> {
+    fn default() -> Matrix4<f64> {
+        Matrix4::identity()
+    }
+}
+
+impl Matrix4<f64> {
+    pub fn identity() -> Matrix4<f64> {
+        Matrix4::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| if i == j { 1.0 } else { 0.0 })
+        }))
+    }
+
+    pub fn translation(t: &Vector3<f64>) -> Matrix4<f64> {
+        let mut m = Matrix4::identity();
+        for i in 0..3 {
+            m.m[i][3] = t[i];
+        }
+        m
+    }
+
+    pub fn scaling(s: &Vector3<f64>) -> Matrix4<f64> {
+        let mut m = Matrix4::identity();
+        for i in 0..3 {
+            m.m[i][i] = s[i];
+        }
+        m
+    }
+
+    /// counter clockwise by `angle` radians when looking down `axis`
+    pub fn rotation(axis: &Vector3<f64>, angle: f64) -> Matrix4<f64> {
+        Quaternion::from_axis_angle(axis, angle).to_matrix()
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        // laplace expansion along 2x2 minors of the top and bottom rows
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    /// gauss-jordan elimination with partial pivoting, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4<f64>> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-300 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+
+    /// cofactors of the upper 3x3 block, the inverse transpose times the determinant. it
+    /// transforms normals like the inverse transpose does, but exists for singular
+    /// matrices too and flips normals along with the winding of mirrored faces
+    pub fn cofactor(&self) -> Matrix4<f64> {
+        let m = &self.m;
+        let mut result = Matrix4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+                let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+                result.m[i][j] = m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+            }
+        }
+        result
+    }
+}
+
+/// invertible affine transform, keeps its inverse around for normals and for going back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4<f64>,
+    inverse: Matrix4<f64>,
+    /// inverse transpose, for normals
+    normal: Matrix4<f64>,
+    determinant: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::from_matrices(Matrix4::identity(), Matrix4::identity())
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform::default()
+    }
+
+    /// None if `matrix` can't be inverted
+    pub fn new(matrix: Matrix4<f64>) -> Option<Transform> {
+        Some(Transform::from_matrices(matrix, matrix.inverse()?))
+    }
+
+    /// `inverse` has to be the inverse of `matrix`
+    fn from_matrices(matrix: Matrix4<f64>, inverse: Matrix4<f64>) -> Transform {
+        Transform {
+            normal: inverse.transpose(),
+            determinant: matrix.determinant(),
+            matrix,
+            inverse,
+        }
+    }
+
+    pub fn translate(t: &Vector3<f64>) -> Transform {
+        Transform::from_matrices(Matrix4::translation(t), Matrix4::translation(&-t))
+    }
+
+    /// None if any factor is 0
+    pub fn scale(s: &Vector3<f64>) -> Option<Transform> {
+        Transform::new(Matrix4::scaling(s))
+    }
+
+    pub fn rotate(rotation: &Quaternion) -> Transform {
+        let matrix = rotation.to_matrix();
+        // rotations are orthogonal
+        Transform::from_matrices(matrix, matrix.transpose())
+    }
+
+    pub fn matrix(&self) -> &Matrix4<f64> {
+        &self.matrix
+    }
+
+    pub fn inverse_matrix(&self) -> &Matrix4<f64> {
+        &self.inverse
+    }
+
+    /// of the matrix, how much it scales volumes and negative if it mirrors
+    pub fn determinant(&self) -> f64 {
+        self.determinant
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform::from_matrices(self.inverse, self.matrix)
+    }
+
+    /// applies `self` and then `next`, same as `next * self`
+    pub fn then(&self, next: &Transform) -> Transform {
+        *next * *self
+    }
+
+    pub fn point(&self, p: &Vector3<f64>) -> Vector3<f64> {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn direction(&self, v: &Vector3<f64>) -> Vector3<f64> {
+        self.matrix.transform_direction(v)
+    }
+
+    /// through the inverse transpose so that normals stay perpendicular to the surface,
+    /// the result is not normalized
+    pub fn normal(&self, n: &Vector3<f64>) -> Vector3<f64> {
+        self.normal.transform_direction(n)
+    }
+}
+
+/// `a * b` applies `b` first
+impl Mul for Transform {
+    type Output = Transform;
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform::from_matrices(self.matrix * rhs.matrix, rhs.inverse * self.inverse)
+    }
+}
+
+/// rotation as a unit quaternion, w is the real part
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quaternion {
+    fn default() -> Quaternion {
+        Quaternion::identity()
+    }
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// counter clockwise by `angle` radians when looking down `axis`
+    pub fn from_axis_angle(axis: &Vector3<f64>, angle: f64) -> Quaternion {
+        let axis = axis.normalized();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quaternion {
+            w: cos,
+            x: axis[0] * sin,
+            y: axis[1] * sin,
+            z: axis[2] * sin,
+        }
+    }
+
+    pub fn dot(&self, rhs: &Quaternion) -> f64 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn len(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let inv = 1.0 / self.len();
+        Quaternion {
+            w: self.w * inv,
+            x: self.x * inv,
+            y: self.y * inv,
+            z: self.z * inv,
+        }
+    }
+
+    /// the inverse rotation for unit quaternions
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    pub fn rotate(&self, v: &Vector3<f64>) -> Vector3<f64> {
+        let p = Quaternion {
+            w: 0.0,
+            x: v[0],
+            y: v[1],
+            z: v[2],
+        };
+        let r = *self * p * self.conjugate();
+        Vector3::new(r.x, r.y, r.z)
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f64> {
+        let Quaternion { w, x, y, z } = self.normalized();
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// constant speed interpolation along the shorter arc, `t` of 0 is `self` and 1 is
+    /// `other`
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut cos = self.dot(other);
+        // q and -q are the same rotation
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Quaternion {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            }
+        } else {
+            *other
+        };
+        let (a, b) = if cos > 1.0 - 1e-9 {
+            // nearly parallel, lerp avoids dividing by a tiny sine
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        }
+        .normalized()
+    }
+}
+
+/// hamilton product, `a * b` rotates by `b` first
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
@@ -1,20 +1,1058 @@
 #[cfg(test)]
 mod tests {
-    use ray_tracer::{renderer::Renderer, vector::Vector3};
+    use ray_tracer::{
+        camera::Camera,
+        filter::Filter,
+        image::{srgb_oetf, ToneMap, ToneMapping},
+        obj::{LoadOptions, ObjError},
+        recorder::{ObjPathRecorder, PathFilter},
+        renderer::{Integrator, RenderSettings, Renderer},
+        scene::{
+            instance::Instance,
+            mesh::Material,
+            shape::{Plane, Sphere},
+            Hit, Scene,
+        },
+        scene_file::{CameraDesc, SceneFile, SceneFileError},
+        texture::{Texture, WrapMode},
+        vector::{Matrix4, Quaternion, Transform, Vector3},
+    };
+
+    /// a folder of its own in the system's temp dir, removed again when dropped so that
+    /// a failing assert doesn't leave files behind
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path =
+                std::env::temp_dir().join(format!("ray-tracer-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn join(&self, file: &str) -> std::path::PathBuf {
+            self.0.join(file)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
 
     #[test]
     fn test1() {
-        let mut renderer = Renderer::new(
+        let camera = Camera::new(
             Vector3::<f64>::new(0.0, -1500.0, 160.0),
-            Vector3::<f64>::new(-80.0, -1400.0, 200.0),
-            160,
-            (16, 9),
-            0.001,
-            4,
-            100,
+            Vector3::<f64>::new(0.0, -1400.0, 155.0),
+            Vector3::<f64>::new(0.0, 0.0, 1.0),
+            48.5,
+            (160, 90),
         );
+        let settings = RenderSettings::builder().spp(100).build().unwrap();
+        let mut renderer = Renderer::new(camera, settings).unwrap();
         renderer.load_obj("assets/lightknight.obj").unwrap();
         let image = renderer.render();
         image.write_to_png("img.png").unwrap();
     }
+
+    #[test]
+    fn thread_count_does_not_change_image() {
+        let render = |threads| {
+            let camera = Camera::new(
+                Vector3::<f64>::new(0.0, -1500.0, 160.0),
+                Vector3::<f64>::new(0.0, -1400.0, 170.0),
+                Vector3::<f64>::new(0.0, 0.0, 1.0),
+                20.0,
+                (40, 22),
+            );
+            let settings = RenderSettings::builder()
+                .spp(4)
+                .seed(7)
+                .threads(threads)
+                .build()
+                .unwrap();
+            let mut renderer = Renderer::new(camera, settings).unwrap();
+            renderer.load_obj("assets/lightknight.obj").unwrap();
+            renderer.render()
+        };
+        let single = render(1);
+        let multi = render(5);
+        let (w, h) = single.shape();
+        for i in 0..w {
+            for j in 0..h {
+                for c in 0..3 {
+                    assert_eq!(single[(i, j)][c].to_bits(), multi[(i, j)][c].to_bits());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn texture_wrap_modes() {
+        // a 2x1 texture, black on the left and white on the right
+        let texture = |wrap| {
+            let texels = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0)];
+            Texture::new(texels, (2, 1), wrap)
+        };
+        let repeat = texture(WrapMode::Repeat);
+        let clamp = texture(WrapMode::Clamp);
+        let mirror = texture(WrapMode::Mirror);
+
+        // texel centers and halfway between them
+        assert_eq!(repeat.sample((0.25, 0.5)).x(), 0.0);
+        assert_eq!(repeat.sample((0.75, 0.5)).x(), 1.0);
+        assert_eq!(repeat.sample((0.5, 0.5)).x(), 0.5);
+        // the left edge blends with the opposite side, or not at all
+        assert_eq!(repeat.sample((0.0, 0.5)).x(), 0.5);
+        assert_eq!(clamp.sample((0.0, 0.5)).x(), 0.0);
+        assert_eq!(mirror.sample((0.0, 0.5)).x(), 0.0);
+        // one texture width further along
+        assert_eq!(repeat.sample((1.25, 0.5)).x(), 0.0);
+        assert_eq!(clamp.sample((1.25, 0.5)).x(), 1.0);
+        assert_eq!(mirror.sample((1.25, 0.5)).x(), 1.0);
+    }
+
+    #[test]
+    fn obj_errors_carry_file_and_line() {
+        let dir = TempDir::new("obj");
+        let load = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            Scene::from_obj(path.to_str().unwrap())
+        };
+
+        // tabs, repeated spaces, comments and line continuations are fine
+        let scene = load(
+            "ok.obj",
+            "v 0 0 0 # origin\nv\t1  0 0\nv 0 1 \\\n 0\nvn 0 0 1\nf 1//1 2//1 3//1\n",
+        )
+        .unwrap();
+        assert_eq!(scene.triangles.len(), 1);
+
+        let err = load("number.obj", "v 0 0 0\n\nv 1 x 0\n").unwrap_err();
+        assert!(
+            matches!(err, ObjError::InvalidNumber { line: 3, .. }),
+            "{err}"
+        );
+        assert!(err.file().ends_with("number.obj"));
+
+        let err = load("index.obj", "v 0 0 0\nvn 0 0 1\nf 1//1 2//1 3//1\n").unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ObjError::IndexOutOfRange {
+                    line: 3,
+                    index: 2,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+
+        let err = load("material.obj", "usemtl missing\n").unwrap_err();
+        assert!(
+            matches!(err, ObjError::UnknownMaterial { line: 1, .. }),
+            "{err}"
+        );
+
+        let err = load("mtllib.obj", "mtllib missing.mtl\n").unwrap_err();
+        assert!(matches!(err, ObjError::Io { .. }), "{err}");
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let dir = TempDir::new("ngon");
+        let path = dir.join("ngon.obj");
+        // a unit quad, then an l shape whose reflex corner breaks a fan from the first vertex
+        std::fs::write(
+            &path,
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             v 2 1 0\nv 2 2 0\nv 0 2 0\nv 0 0 0\nv 1 0 0\nv 1 1 0\n\
+             vn 0 0 1\n\
+             f 1//1 2//1 3//1 4//1\n\
+             f 5//1 6//1 7//1 8//1 9//1 10//1\n",
+        )
+        .unwrap();
+        let scene = Scene::from_obj(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(scene.triangles.len(), 2 + 4);
+        // vertices are loaded in centimeters
+        let area = |range: std::ops::Range<usize>| {
+            range.map(|t| scene.get_triangle_area(t)).sum::<f64>() / 100.0 / 100.0
+        };
+        assert!((area(0..2) - 1.0).abs() < 1e-9);
+        assert!((area(2..6) - 3.0).abs() < 1e-9);
+        // the winding, and so the face normal, is kept
+        for t in 0..6 {
+            assert!(scene.get_face_normal(t).z() > 0.0);
+        }
+    }
+
+    #[test]
+    fn faces_without_normals() {
+        let dir = TempDir::new("faces");
+        let path = dir.join("faces.obj");
+        std::fs::write(
+            &path,
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvt 0 0\nvt 1 0\nvt 0 1\n\
+             f 1 2 3\n\
+             f -4/-3 -3/-2 -2/-1\n\
+             s 1\n\
+             f 1 3 4\n\
+             f 1 4 2\n",
+        )
+        .unwrap();
+        let scene = Scene::from_obj(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(scene.triangles.len(), 4);
+        assert_eq!(scene.triangles[1].vertices, [0, 1, 2]);
+        assert_eq!(scene.triangles[1].texture_coords, Some([0, 1, 2]));
+        // without smoothing the face normal is used at every corner
+        for t in 0..2 {
+            for n in scene.triangles[t].point_normals {
+                assert_eq!(scene.point_normals[n].z(), 1.0);
+            }
+        }
+        // the smoothing group shares one normal at the corner both faces touch
+        let (a, b) = (&scene.triangles[2], &scene.triangles[3]);
+        assert_eq!(a.point_normals[0], b.point_normals[0]);
+        let shared = &scene.point_normals[a.point_normals[0]];
+        let expected = Vector3::new(1.0, 1.0, 0.0).normalized();
+        assert!((shared - &expected).len() < 1e-9, "{shared:?}");
+    }
+
+    #[test]
+    fn load_options_transform_vertices_and_normals() {
+        let dir = TempDir::new("units");
+        let path = dir.join("units.obj");
+        std::fs::write(
+            &path,
+            "v 0 0 0\nv 1 0 0\nv 0 0 -1\nvn 0 1 0\nf 1//1 2//1 3//1\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        // y up and meters in the file, z up and centimeters in the scene
+        let options = LoadOptions {
+            y_up: true,
+            ..Default::default()
+        };
+        let scene = Scene::from_obj_with(path, &options).unwrap();
+        assert_eq!(scene.vertices[1].as_vec(), vec![100.0, 0.0, 0.0]);
+        assert_eq!(scene.vertices[2].as_vec(), vec![0.0, 100.0, 0.0]);
+        assert_eq!(scene.point_normals[0].as_vec(), vec![0.0, 0.0, 1.0]);
+        assert_eq!(scene.get_face_normal(0).as_vec(), vec![0.0, 0.0, 1.0]);
+
+        // mirroring turns the face around, the winding is swapped to keep it facing +z
+        let options = LoadOptions {
+            scale: 1.0,
+            y_up: true,
+            transform: Matrix4::new([
+                [-1.0, 0.0, 0.0, 5.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 2.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ]),
+        };
+        let scene = Scene::from_obj_with(path, &options).unwrap();
+        assert_eq!(scene.vertices[1].as_vec(), vec![4.0, 0.0, 0.0]);
+        assert_eq!(scene.point_normals[0].as_vec(), vec![0.0, 0.0, 1.0]);
+        assert_eq!(scene.get_face_normal(0).as_vec(), vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn mirrored_meshes_stay_outside_out() {
+        let dir = TempDir::new("mirror");
+        let path = dir.join("cube.obj");
+        std::fs::write(
+            &path,
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+             vn 0 0 -1\nvn 0 0 1\nvn 0 -1 0\nvn 0 1 0\nvn -1 0 0\nvn 1 0 0\n\
+             f 1//1 4//1 3//1 2//1\nf 5//2 6//2 7//2 8//2\nf 1//3 2//3 6//3 5//3\n\
+             f 3//4 4//4 8//4 7//4\nf 1//5 5//5 8//5 4//5\nf 2//6 3//6 7//6 6//6\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        for transform in [
+            Matrix4::identity(),
+            Matrix4::scaling(&Vector3::new(-1.0, 1.0, 1.0)),
+        ] {
+            let options = LoadOptions {
+                transform,
+                ..Default::default()
+            };
+            let scene = Scene::from_obj_with(path, &options).unwrap();
+            let center = scene
+                .vertices
+                .iter()
+                .fold(Vector3::default(), |sum, v| sum + v)
+                * (1.0 / scene.vertices.len() as f64);
+            assert_eq!(scene.triangles.len(), 12);
+            for (i, triangle) in scene.triangles.iter().enumerate() {
+                let outward = (0..3)
+                    .map(|v| scene.get_triangle_vertex(i, v) - &center)
+                    .fold(Vector3::default(), |sum, v| sum + v);
+                assert!(scene.get_face_normal(i).dot(&outward) > 0.0, "{i}");
+                for normal in triangle.point_normals {
+                    assert!(scene.point_normals[normal].dot(&outward) > 0.0, "{i}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transforms_and_quaternions() {
+        let close = |a: &Vector3<f64>, b: &Vector3<f64>| (a - b).len() < 1e-9;
+        let z = Vector3::new(0.0, 0.0, 1.0);
+        let quarter = Quaternion::from_axis_angle(&z, std::f64::consts::FRAC_PI_2);
+        assert!(close(
+            &quarter.rotate(&Vector3::new(1.0, 0.0, 0.0)),
+            &Vector3::new(0.0, 1.0, 0.0)
+        ));
+
+        // scale, then rotate, then move
+        let transform = Transform::scale(&Vector3::new(2.0, 1.0, 1.0))
+            .unwrap()
+            .then(&Transform::rotate(&quarter))
+            .then(&Transform::translate(&Vector3::new(0.0, 0.0, 5.0)));
+        let p = Vector3::new(1.0, 1.0, 0.0);
+        assert!(close(&transform.point(&p), &Vector3::new(-1.0, 2.0, 5.0)));
+        assert!(close(&transform.inverse().point(&transform.point(&p)), &p));
+        let product = *transform.matrix() * *transform.inverse_matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product[(i, j)] - expected).abs() < 1e-12);
+            }
+        }
+
+        // normals stay perpendicular to the tangents of a stretched surface
+        let (tangent, normal) = (Vector3::new(1.0, -1.0, 0.0), Vector3::new(1.0, 1.0, 0.0));
+        let dot = transform
+            .direction(&tangent)
+            .dot(&transform.normal(&normal));
+        assert!(dot.abs() < 1e-12);
+
+        // halfway between no rotation and a quarter turn is an eighth of a turn
+        let half = Quaternion::identity().slerp(&quarter, 0.5);
+        let eighth = Quaternion::from_axis_angle(&z, std::f64::consts::FRAC_PI_4);
+        assert!((half.dot(&eighth).abs() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn instances_share_a_prototype() {
+        let dir = TempDir::new("instances");
+        let path = dir.join("instances.obj");
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let options = LoadOptions {
+            scale: 1.0,
+            ..Default::default()
+        };
+        let mut scene = Scene::from_obj_with(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(scene.prototypes.len(), 1);
+        assert!(scene.lights.is_empty());
+
+        // a second copy, twice as large, turned upside down and glowing
+        scene.materials.push(Material {
+            emission: Vector3::new(1.0, 1.0, 1.0),
+            ..Default::default()
+        });
+        let flip = Quaternion::from_axis_angle(&Vector3::new(1.0, 0.0, 0.0), std::f64::consts::PI);
+        let mut instance = Instance::new(
+            0,
+            Transform::scale(&Vector3::new(2.0, 2.0, 2.0))
+                .unwrap()
+                .then(&Transform::rotate(&flip))
+                .then(&Transform::translate(&Vector3::new(0.0, 0.0, 3.0))),
+        );
+        instance.material = Some(scene.materials.len() - 1);
+        let index = scene.add_instance(instance);
+        scene.build();
+
+        assert_eq!(scene.triangles.len(), 1);
+        assert_eq!(scene.lights, vec![(index, 0)]);
+        assert!((scene.get_world_triangle_area(index, 0) - 2.0).abs() < 1e-9);
+        let hit = |instance| Hit {
+            t: 1.0,
+            instance,
+            triangle: 0,
+            u: 0.25,
+            v: 0.25,
+        };
+        assert!((scene.light_pdf(&hit(index)) - 0.5).abs() < 1e-9);
+        assert_eq!(scene.light_pdf(&hit(0)), 0.0);
+        let normal = scene.get_world_face_normal(index, 0);
+        assert!((normal.z() + 1.0).abs() < 1e-9, "{normal:?}");
+        assert!(scene.bvh.bounds().max.z() > 2.9);
+    }
+
+    #[test]
+    fn analytic_shapes() {
+        let mut scene = Scene::default();
+        scene.materials.push(Material {
+            diffuse: Vector3::new(0.8, 0.8, 0.8),
+            ..Default::default()
+        });
+        scene.materials.push(Material {
+            emission: Vector3::new(1.0, 1.0, 1.0),
+            ..Default::default()
+        });
+        // a glowing ball of radius two resting on an infinite floor
+        let sphere = scene.add_shape(Sphere::new(1.0), 1);
+        let plane = scene.add_shape(Plane, 0);
+        let ball = scene.add_instance(Instance::new(
+            sphere,
+            Transform::scale(&Vector3::new(2.0, 2.0, 2.0)).unwrap(),
+        ));
+        scene.add_instance(Instance::new(
+            plane,
+            Transform::translate(&Vector3::new(0.0, 0.0, -2.0)),
+        ));
+        scene.build();
+
+        assert_eq!(scene.lights, vec![(ball, 0)]);
+        let hit = Hit {
+            t: 1.0,
+            instance: ball,
+            triangle: 0,
+            u: 0.3,
+            v: 0.6,
+        };
+        let area = 4.0 * std::f64::consts::PI * 4.0;
+        assert!((scene.light_pdf(&hit) * area - 1.0).abs() < 1e-9);
+        let normal = scene.get_geometric_normal(&hit);
+        assert!((normal.len() - 1.0).abs() < 1e-9);
+
+        // the ball fills the middle, the floor only the lower half of the frame
+        let camera = Camera::new(
+            Vector3::new(0.0, -20.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            20.0,
+            (9, 9),
+        );
+        let settings = RenderSettings::builder().spp(4).threads(1).build();
+        let mut renderer = Renderer::new(camera, settings.unwrap()).unwrap();
+        renderer.set_scene(scene);
+        let image = renderer.render();
+        assert!(image[(4, 4)].x() >= 1.0);
+        let (top, bottom) = (image[(0, 0)].x(), image[(0, 8)].x());
+        assert_eq!(top.min(bottom), 0.0);
+        assert!(top.max(bottom) > 0.0);
+    }
+
+    #[test]
+    fn scene_files() {
+        let dir = TempDir::new("scene");
+        std::fs::write(
+            dir.join("quad.obj"),
+            "o a\nv 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\no b\nv 0 1 0\nf 1 3 4\n",
+        )
+        .unwrap();
+        let path = dir.join("scene.toml");
+        std::fs::write(
+            &path,
+            r#"
+[camera]
+eye = [0.0, -500.0, 50.0]
+target = [0.0, 0.0, 50.0]
+resolution = [8, 6]
+
+[render]
+spp = 2
+seed = 3
+filter = { type = "gaussian", radius = 1.5, alpha = 2.0 }
+
+[output]
+png = "out.png"
+
+[[materials]]
+name = "red"
+diffuse = [0.8, 0.1, 0.1]
+
+[[meshes]]
+name = "quad"
+path = "quad.obj"
+
+[[shapes]]
+name = "ball"
+material = "red"
+shape = { type = "sphere", radius = 20.0 }
+
+[[instances]]
+object = "quad"
+material = "red"
+transform = { degrees = 90.0, axis = [1.0, 0.0, 0.0] }
+
+[[instances]]
+object = "ball"
+transform = { translate = [0.0, 0.0, 150.0] }
+
+[[lights]]
+emission = [4.0, 4.0, 4.0]
+shape = { type = "disk", radius = 30.0 }
+transform = { translate = [0.0, -100.0, 300.0], scale = [1.0, 1.0, -1.0] }
+"#,
+        )
+        .unwrap();
+        let file = SceneFile::load(path.to_str().unwrap()).unwrap();
+        let scene = file.build_scene().unwrap();
+        // both groups of the mesh, the ball and the light
+        assert_eq!(scene.instances.len(), 4);
+        assert_eq!(scene.prototypes.len(), 4);
+        assert_eq!(scene.lights.len(), 1);
+        assert_eq!(scene.instances[0].material, Some(0));
+
+        // saving and loading again gives back the same description
+        let saved = dir.join("saved.toml");
+        file.save(saved.to_str().unwrap()).unwrap();
+        assert_eq!(SceneFile::load(saved.to_str().unwrap()).unwrap(), file);
+
+        let image = file.renderer().unwrap().render();
+        assert_eq!(image.shape(), (8, 6));
+        file.write_outputs(&image).unwrap();
+        assert!(dir.join("out.png").exists());
+
+        let err = SceneFile::from_toml("[camera]\nfov = 3\n").unwrap_err();
+        assert!(err.contains("fov"), "{err}");
+        let mut broken = file.clone();
+        broken.instances[1].material = Some("blue".to_string());
+        assert!(matches!(
+            broken.build_scene(),
+            Err(SceneFileError::UnknownName {
+                kind: "material",
+                ..
+            })
+        ));
+
+        // cameras that cannot see anything fail instead of rendering black
+        let cameras: [fn(&mut CameraDesc); 4] = [
+            |camera| camera.resolution = [0, 6],
+            |camera| camera.eye = camera.target,
+            |camera| camera.vfov = 180.0,
+            |camera| camera.focus_distance = Some(0.0),
+        ];
+        for change in cameras {
+            let mut broken = file.clone();
+            change(&mut broken.camera);
+            assert!(matches!(
+                broken.build_scene(),
+                Err(SceneFileError::InvalidCamera { .. })
+            ));
+            assert!(broken.camera.to_camera().is_err());
+        }
+        // like `Camera::new`, looking along `up` falls back to another axis
+        let mut along_up = file.clone();
+        along_up.camera.up = [0.0, 2.0, 0.0];
+        assert!(along_up.build_scene().is_ok());
+        assert!(along_up.camera.to_camera().is_ok());
+    }
+
+    #[test]
+    fn command_line_exit_codes() {
+        let run = |args: &[&str]| {
+            std::process::Command::new(env!("CARGO_BIN_EXE_ray-tracer"))
+                .args(args)
+                .output()
+                .unwrap()
+                .status
+                .code()
+        };
+        let dir = TempDir::new("cli");
+        let obj = dir.join("tri.obj");
+        std::fs::write(&obj, "v 0 0 0\nv 1 0 0\nv 0 0 1\nf 1 2 3\n").unwrap();
+        let (obj, out) = (obj.to_str().unwrap(), dir.join("out.pfm"));
+        let out = out.to_str().unwrap();
+
+        assert_eq!(run(&["info", obj]), Some(0));
+        let render = [
+            "render",
+            obj,
+            "--resolution",
+            "4x3",
+            "--spp",
+            "1",
+            "--output",
+            out,
+        ];
+        assert_eq!(run(&render), Some(0));
+        assert!(std::path::Path::new(out).exists());
+        assert_eq!(run(&["info", "missing.obj"]), Some(1));
+        let empty = dir.join("empty.toml");
+        std::fs::write(&empty, "[camera]\nresolution = [0, 0]\n").unwrap();
+        let empty = ["render", empty.to_str().unwrap(), "--output", out];
+        assert_eq!(run(&empty), Some(1));
+
+        // a scene without outputs renders for nothing, and scene files keep their scales
+        let scene = dir.join("ball.toml");
+        std::fs::write(
+            &scene,
+            "[[materials]]\nname = \"grey\"\n\
+             [[shapes]]\nname = \"ball\"\nmaterial = \"grey\"\n\
+             shape = { type = \"sphere\", radius = 1.0 }\n\
+             [[instances]]\nobject = \"ball\"\n",
+        )
+        .unwrap();
+        let scene = scene.to_str().unwrap();
+        assert_eq!(run(&["render", scene]), Some(2));
+        assert_eq!(run(&["render", obj, "--spp", "1"]), Some(2));
+        let reversed = ["render", obj, "--output", out, "--paths-pixels", "10,0,2,3"];
+        assert_eq!(run(&reversed), Some(2));
+        let copy = dir.join("copy.toml");
+        let copy = copy.to_str().unwrap();
+        assert_eq!(run(&["convert", scene, copy, "--scale", "2"]), Some(2));
+        assert_eq!(run(&["convert", scene, copy]), Some(0));
+        // the sphere can't be written as triangles, which is said rather than hidden
+        let ball = dir.join("ball.obj");
+        let converted = std::process::Command::new(env!("CARGO_BIN_EXE_ray-tracer"))
+            .args(["convert", scene, ball.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert!(converted.status.success());
+        let stderr = String::from_utf8_lossy(&converted.stderr);
+        assert!(stderr.contains("analytic shapes"), "{stderr}");
+        assert_eq!(run(&["render", obj, "--spp", "many"]), Some(2));
+        assert_eq!(run(&["frobnicate"]), Some(2));
+        let unwritable = ["convert", obj, "/nonexistent/tri.obj"];
+        assert_eq!(run(&unwritable), Some(3));
+    }
+
+    #[test]
+    fn path_recorder_writes_only_filtered_paths() {
+        let dir = TempDir::new("paths");
+        let path = dir.join("paths.obj");
+        let camera = Camera::new(
+            Vector3::<f64>::new(0.0, -1500.0, 160.0),
+            Vector3::<f64>::new(0.0, -1400.0, 170.0),
+            Vector3::<f64>::new(0.0, 0.0, 1.0),
+            20.0,
+            (16, 9),
+        );
+        let settings = RenderSettings::builder().spp(4).build().unwrap();
+        let mut renderer = Renderer::new(camera, settings).unwrap();
+        renderer.load_obj("assets/lightknight.obj").unwrap();
+        let filter = PathFilter {
+            pixels: Some((8..10, 4..5)),
+            samples: Some(1..3),
+        };
+        let recorder = ObjPathRecorder::new(path.to_str().unwrap(), filter);
+        let recorded = renderer.render_recorded(&recorder).unwrap();
+        // recording does not change the image
+        let image = renderer.render();
+        let (w, h) = image.shape();
+        for i in 0..w {
+            for j in 0..h {
+                for c in 0..3 {
+                    assert_eq!(image[(i, j)][c].to_bits(), recorded[(i, j)][c].to_bits());
+                }
+            }
+        }
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let names: Vec<_> = text.lines().filter(|l| l.starts_with("o ")).collect();
+        assert_eq!(
+            names,
+            [
+                "o path_8_4_1",
+                "o path_8_4_2",
+                "o path_9_4_1",
+                "o path_9_4_2"
+            ]
+        );
+        assert_eq!(
+            text.lines().filter(|l| l.starts_with("l ")).count(),
+            names.len()
+        );
+    }
+
+    #[test]
+    fn render_settings() {
+        let settings = RenderSettings::builder()
+            .spp(1024)
+            .max_depth(12)
+            .rr_depth(5)
+            .seed(11)
+            .threads(2)
+            .clamp(10.0)
+            .integrator(Integrator::Direct)
+            .build()
+            .unwrap();
+        assert_eq!(settings.spp, 1024);
+        let text = settings.to_toml().unwrap();
+        assert_eq!(RenderSettings::from_toml(&text).unwrap(), settings);
+
+        for invalid in [
+            RenderSettings::builder().spp(0),
+            RenderSettings::builder().max_depth(0),
+            RenderSettings::builder().threads(0),
+            RenderSettings::builder().clamp(-1.0),
+            RenderSettings::builder().acne_threshold(f64::NAN),
+        ] {
+            assert!(invalid.build().is_err());
+        }
+        let err = RenderSettings::from_toml("spp = 0\n").unwrap_err();
+        assert!(err.contains("spp"), "{err}");
+        // the names used before the settings had their own type still load
+        let old = RenderSettings::from_toml("rays_per_pixel = 8\nmax_bounces = 3\n").unwrap();
+        assert_eq!((old.spp, old.max_depth), (8, 3));
+        assert_eq!(
+            "normals".parse::<Integrator>().unwrap().to_string(),
+            "normals"
+        );
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_average() {
+        let mean = |rr_depth| {
+            let camera = Camera::new(
+                Vector3::<f64>::new(0.0, -1500.0, 160.0),
+                Vector3::<f64>::new(0.0, -1400.0, 155.0),
+                Vector3::<f64>::new(0.0, 0.0, 1.0),
+                48.5,
+                (32, 18),
+            );
+            let settings = RenderSettings::builder()
+                .spp(64)
+                .max_depth(8)
+                .rr_depth(rr_depth)
+                .build()
+                .unwrap();
+            let mut renderer = Renderer::new(camera, settings).unwrap();
+            renderer.load_obj("assets/lightknight.obj").unwrap();
+            let image = renderer.render();
+            let (w, h) = image.shape();
+            let mut sum = 0.0;
+            for i in 0..w {
+                for j in 0..h {
+                    sum += image[(i, j)].x() + image[(i, j)].y() + image[(i, j)].z();
+                }
+            }
+            sum / (w * h) as f64
+        };
+        // ending paths at random darkens nothing on average, it only adds noise
+        let (roulette, full) = (mean(1), mean(8));
+        assert!((roulette / full - 1.0).abs() < 0.05, "{roulette} vs {full}");
+    }
+
+    #[test]
+    fn furnace_converges_with_light_sampling() {
+        // inside a sphere that glows with 0.5 and reflects half of what arrives, every
+        // direction sees 0.5 + 0.25 + 0.125 + ... = 1
+        let mean = |integrator| {
+            let mut scene = Scene::default();
+            scene.materials.push(Material {
+                diffuse: Vector3::new(0.5, 0.5, 0.5),
+                emission: Vector3::new(0.5, 0.5, 0.5),
+                ..Default::default()
+            });
+            let sphere = scene.add_shape(Sphere::new(100.0), 0);
+            scene.add_instance(Instance::new(sphere, Transform::identity()));
+            let camera = Camera::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+                90.0,
+                (8, 8),
+            );
+            let settings = RenderSettings::builder()
+                .spp(64)
+                .integrator(integrator)
+                .build();
+            let mut renderer = Renderer::new(camera, settings.unwrap()).unwrap();
+            renderer.set_scene(scene);
+            let image = renderer.render();
+            let (w, h) = image.shape();
+            let mut sum = 0.0;
+            for i in 0..w {
+                for j in 0..h {
+                    sum += image[(i, j)].x();
+                }
+            }
+            sum / (w * h) as f64
+        };
+        let path = mean(Integrator::Path);
+        assert!((path - 1.0).abs() < 0.02, "{path}");
+        // emission plus one bounce
+        let direct = mean(Integrator::Direct);
+        assert!((direct - 0.75).abs() < 0.02, "{direct}");
+    }
+
+    #[test]
+    fn glowing_plane_behind_a_bounce() {
+        // a diffuse floor under a glowing ceiling, the sphere keeps the light list from
+        // being empty so that bounces hitting the ceiling are weighted with mis
+        let mut scene = Scene::default();
+        scene.materials.push(Material {
+            diffuse: Vector3::new(0.5, 0.5, 0.5),
+            ..Default::default()
+        });
+        scene.materials.push(Material {
+            emission: Vector3::new(1.0, 1.0, 1.0),
+            ..Default::default()
+        });
+        let floor = scene.add_shape(Plane, 0);
+        scene.add_instance(Instance::new(floor, Transform::identity()));
+        let ceiling = scene.add_shape(Plane, 1);
+        let flip = Transform::scale(&Vector3::new(1.0, 1.0, -1.0)).unwrap();
+        let up = Transform::translate(&Vector3::new(0.0, 0.0, 1000.0));
+        scene.add_instance(Instance::new(ceiling, flip.then(&up)));
+        let lamp = scene.add_shape(Sphere::new(10.0), 1);
+        let above = Transform::translate(&Vector3::new(0.0, 0.0, 200.0));
+        scene.add_instance(Instance::new(lamp, above));
+
+        let camera = Camera::new(
+            Vector3::new(0.0, -500.0, 300.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            40.0,
+            (8, 6),
+        );
+        let settings = RenderSettings::builder().spp(8).build().unwrap();
+        let mut renderer = Renderer::new(camera, settings).unwrap();
+        renderer.set_scene(scene);
+        let image = renderer.render();
+        let (w, h) = image.shape();
+        for i in 0..w {
+            for j in 0..h {
+                let pixel = &image[(i, j)];
+                assert!(pixel.as_vec().iter().all(|c| c.is_finite()), "{pixel:?}");
+            }
+        }
+        // the floor is lit
+        assert!(image[(w / 2, h - 1)].x() > 0.0);
+    }
+
+    #[test]
+    fn camera_looking_along_up() {
+        let mut scene = Scene::default();
+        scene.materials.push(Material {
+            emission: Vector3::new(1.0, 1.0, 1.0),
+            ..Default::default()
+        });
+        let plane = scene.add_shape(Plane, 0);
+        scene.add_instance(Instance::new(plane, Transform::identity()));
+        // straight down onto a glowing floor, with the default z up
+        let camera = Camera::new(
+            Vector3::new(0.0, 0.0, 500.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            40.0,
+            (4, 3),
+        );
+        let settings = RenderSettings::builder().spp(1).build().unwrap();
+        let mut renderer = Renderer::new(camera, settings).unwrap();
+        renderer.set_scene(scene);
+        let image = renderer.render();
+        for i in 0..4 {
+            for j in 0..3 {
+                assert_eq!(image[(i, j)].x(), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "resolution")]
+    fn camera_without_pixels() {
+        Camera::new(
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            40.0,
+            (0, 6),
+        );
+    }
+
+    #[test]
+    fn filters_vanish_at_their_radius() {
+        for filter in [
+            Filter::box_filter(),
+            Filter::tent(),
+            Filter::gaussian(),
+            Filter::mitchell(),
+        ] {
+            let r = filter.radius();
+            assert!(filter.eval(r, 0.0).abs() < 1e-12, "{filter:?}");
+            assert!(filter.eval(0.0, -r).abs() < 1e-12, "{filter:?}");
+            assert!(filter.eval(2.0 * r, 0.0).abs() < 1e-12, "{filter:?}");
+            assert!(filter.eval(0.9 * r, 0.0) != 0.0, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn tone_mapping() {
+        // both pieces of the srgb curve meet at the threshold
+        let (below, above) = (srgb_oetf(0.0031308), srgb_oetf(0.0031308 + 1e-12));
+        assert!((below - above).abs() < 1e-6, "{below} {above}");
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-12);
+
+        let map = |tone_map, c: f64| {
+            let mapping = ToneMapping {
+                tone_map,
+                ..Default::default()
+            };
+            mapping.apply(&Vector3::new(c, c, c)).x()
+        };
+        for tone_map in [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces] {
+            assert_eq!(map(tone_map, 0.0), 0.0, "{tone_map:?}");
+            assert_eq!(map(tone_map, -1.0), 0.0, "{tone_map:?}");
+            assert_eq!(map(tone_map, f64::NAN), 0.0, "{tone_map:?}");
+            assert!(map(tone_map, 1e6) > 0.99, "{tone_map:?}");
+        }
+        // a gray of luminance l maps to l / (1 + l)
+        assert!((map(ToneMap::Reinhard, 1.0) - 0.5).abs() < 1e-9);
+        assert!((map(ToneMap::Reinhard, 3.0) - 0.75).abs() < 1e-9);
+        // both matrices of the aces fit keep grays gray, leaving only its curve
+        let x = 0.18;
+        let curve =
+            (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081);
+        let gray = map(ToneMap::Aces, x);
+        assert!((gray - curve).abs() < 1e-4, "{gray} {curve}");
+        assert_eq!(map(ToneMap::Clamp, 0.25), 0.25);
+
+        let pixels: Vec<_> = (0..64)
+            .map(|i| Vector3::new(i as f64 / 64.0, 0.5, 2.0))
+            .collect();
+        let dithered = ToneMapping::default();
+        assert_eq!(dithered.quantize(&pixels), dithered.quantize(&pixels));
+        let exact = ToneMapping {
+            dither: false,
+            ..Default::default()
+        };
+        let bytes = exact.quantize(&pixels);
+        assert_eq!(&bytes[..3], &[0, 188, 255]);
+        for (a, b) in bytes.iter().zip(dithered.quantize(&pixels)) {
+            assert!(a.abs_diff(b) <= 1);
+        }
+    }
+
+    #[test]
+    fn interpolated_normals_meet_the_vertex_normals() {
+        let dir = TempDir::new("normals");
+        let path = dir.join("normals.obj");
+        std::fs::write(
+            &path,
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             vn 0 0 1\nvn 1 0 1\nvn 0 -1 2\n\
+             f 1//1 2//2 3//3\n",
+        )
+        .unwrap();
+        let scene = Scene::from_obj(path.to_str().unwrap()).unwrap();
+        let expected = [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 1.0).normalized(),
+            Vector3::new(0.0, -1.0, 2.0).normalized(),
+        ];
+        for (corner, (u, v)) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].into_iter().enumerate() {
+            let hit = Hit {
+                t: 1.0,
+                instance: 0,
+                triangle: 0,
+                u,
+                v,
+            };
+            let normal = scene.get_shading_normal(&hit);
+            assert!(
+                (&normal - &expected[corner]).len() < 1e-9,
+                "{corner} {normal:?}"
+            );
+        }
+        // and stay normalized in between
+        let hit = Hit {
+            t: 1.0,
+            instance: 0,
+            triangle: 0,
+            u: 0.3,
+            v: 0.3,
+        };
+        assert!((scene.get_shading_normal(&hit).len() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn normal_and_bump_maps_keep_their_own_strength() {
+        let dir = TempDir::new("maps");
+        let texture = ray_tracer::image::Image::new((2, 2));
+        texture
+            .write_to_png(dir.join("flat.png").to_str().unwrap())
+            .unwrap();
+        std::fs::write(
+            dir.join("maps.mtl"),
+            "newmtl both\nnorm -bm 0.5 flat.png\nbump -bm 0.2 flat.png\n",
+        )
+        .unwrap();
+        let path = dir.join("maps.obj");
+        std::fs::write(
+            &path,
+            "mtllib maps.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\n\
+             usemtl both\nf 1/1 2/2 3/3\n",
+        )
+        .unwrap();
+
+        // the default options turn meters into centimeters, which only the heights follow
+        let scene = Scene::from_obj(path.to_str().unwrap()).unwrap();
+        let material = scene.get_material(0, 0);
+        assert!(material.normal_map.is_some() && material.bump_map.is_some());
+        assert_eq!(material.normal_strength, 0.5);
+        assert!((material.bump_height - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn normal_and_bump_maps_tilt_the_shading_normal() {
+        let dir = TempDir::new("tilt");
+        // rgb texels, row by row
+        let write_png = |name: &str, width: u32, texels: &[[u8; 3]]| {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            let mut encoder = png::Encoder::new(file, width, texels.len() as u32 / width);
+            encoder.set_color(png::ColorType::Rgb);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(texels.as_flattened()).unwrap();
+        };
+        // heights rising along u, and normals leaning towards +u
+        write_png("ramp.png", 4, &[0, 85, 170, 255].map(|h| [h; 3]));
+        write_png("lean.png", 1, &[[255, 128, 128]]);
+        std::fs::write(
+            dir.join("tilt.mtl"),
+            "newmtl normal\nnorm lean.png\n\
+             newmtl flat_normal\nnorm -bm 0 lean.png\n\
+             newmtl bump\nbump -bm 0.1 ramp.png\n\
+             newmtl flat_bump\nbump -bm 0 ramp.png\n",
+        )
+        .unwrap();
+        let path = dir.join("tilt.obj");
+        let mut obj = "mtllib tilt.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                       vt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n"
+            .to_string();
+        for material in ["normal", "flat_normal", "bump", "flat_bump"] {
+            obj += &format!("usemtl {material}\nf 1/1/1 2/2/1 3/3/1\n");
+        }
+        std::fs::write(&path, obj).unwrap();
+        let scene = Scene::from_obj(path.to_str().unwrap()).unwrap();
+
+        let normal = |triangle: usize| {
+            let hit = Hit {
+                t: 1.0,
+                instance: 0,
+                triangle,
+                u: 1.0 / 3.0,
+                v: 1.0 / 3.0,
+            };
+            scene.get_shading_normal(&hit)
+        };
+        // the normal map leans towards the tangent, the bump map away from the slope
+        let leaning = normal(0);
+        assert!(leaning.x() > 0.5 && leaning.z() > 0.0, "{leaning:?}");
+        let bumped = normal(2);
+        assert!(bumped.x() < -0.1 && bumped.z() > 0.0, "{bumped:?}");
+        // and at strength 0 neither does anything
+        for triangle in [1, 3] {
+            let flat = normal(triangle);
+            assert!(
+                (&flat - &Vector3::new(0.0, 0.0, 1.0)).len() < 1e-9,
+                "{flat:?}"
+            );
+        }
+    }
 }